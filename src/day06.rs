@@ -1,3 +1,4 @@
+#[cfg(test)]
 use std::collections::{HashMap, HashSet};
 
 use regex::Regex;
@@ -7,6 +8,7 @@ fn split_groups(contents: &str) -> Vec<String> {
     contents.split(&separator).map(|x| x.to_string()).collect()
 }
 
+#[cfg(test)]
 fn make_set_union(answers: &str) -> HashSet<char> {
     let mut answerset: HashSet<char> = HashSet::new();
     for c in answers.chars() {
@@ -17,6 +19,7 @@ fn make_set_union(answers: &str) -> HashSet<char> {
     answerset
 }
 
+#[cfg(test)]
 fn make_set_intersection(answers: &str) -> HashSet<char> {
     let people = answers.split('\n');
     let numpeople = people.clone().count();
@@ -38,20 +41,128 @@ fn make_set_intersection(answers: &str) -> HashSet<char> {
     answerset
 }
 
+/// Answers for one customs group, with each person's "yes" answers packed
+/// into a 26-bit set (bit 0 is question 'a', bit 25 is question 'z').
+#[derive(Debug, Clone, PartialEq)]
+struct GroupAnswers {
+    people: Vec<u32>,
+    counts: [usize; 26],
+}
+
+fn question_bit(c: char) -> Option<u32> {
+    if c.is_ascii_lowercase() {
+        Some(1 << (c as u32 - 'a' as u32))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+fn bits_to_set(bits: u32) -> HashSet<char> {
+    ('a'..='z')
+        .filter(|c| bits & question_bit(*c).unwrap() != 0)
+        .collect()
+}
+
+impl GroupAnswers {
+    fn new(group: &str) -> GroupAnswers {
+        let mut people = Vec::new();
+        let mut counts = [0; 26];
+        for person in group.split('\n').map(|x| x.trim()) {
+            if person.is_empty() {
+                continue;
+            }
+            let bits = person
+                .chars()
+                .filter_map(question_bit)
+                .fold(0, |acc, b| acc | b);
+            for (i, count) in counts.iter_mut().enumerate() {
+                if bits & (1 << i) != 0 {
+                    *count += 1;
+                }
+            }
+            people.push(bits);
+        }
+        GroupAnswers { people, counts }
+    }
+
+    fn num_people(&self) -> usize {
+        self.people.len()
+    }
+
+    /// Number of people in the group who answered "yes" to `question`.
+    #[allow(dead_code)]
+    fn count(&self, question: char) -> usize {
+        match question_bit(question) {
+            Some(bit) => self.counts[bit.trailing_zeros() as usize],
+            None => 0,
+        }
+    }
+
+    /// Questions answered "yes" by at least `k` members of the group. A
+    /// question nobody answered never meets the quorum, even for `k == 0`.
+    fn at_least(&self, k: usize) -> u32 {
+        let mut bits = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            if *count >= k.max(1) {
+                bits |= 1 << i;
+            }
+        }
+        bits
+    }
+
+    /// Questions answered "yes" by anyone in the group.
+    fn anyone(&self) -> u32 {
+        self.at_least(1)
+    }
+
+    /// Questions answered "yes" by everyone in the group.
+    fn everyone(&self) -> u32 {
+        self.at_least(self.num_people())
+    }
+
+    /// Questions answered "yes" by exactly one person in the group.
+    #[allow(dead_code)]
+    fn exactly_one(&self) -> u32 {
+        let mut bits = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            if *count == 1 {
+                bits |= 1 << i;
+            }
+        }
+        bits
+    }
+}
+
+fn analyze_groups(contents: &str) -> Vec<GroupAnswers> {
+    split_groups(contents)
+        .iter()
+        .map(|g| GroupAnswers::new(g))
+        .collect()
+}
+
+/// Sum over all groups of the number of questions answered by at least `k`
+/// members of that group.
+#[allow(dead_code)]
+fn quorum_sum(contents: &str, k: usize) -> i64 {
+    analyze_groups(contents)
+        .iter()
+        .map(|g| g.at_least(k).count_ones() as i64)
+        .sum()
+}
+
 fn day06a(contents: &str) -> i64 {
-    let groups = split_groups(contents);
-    let answersets = groups.into_iter().map(|g| make_set_union(&g));
-    let counts = answersets.map(|set| set.len());
-    let sum: usize = counts.sum();
-    sum as i64
+    analyze_groups(contents)
+        .iter()
+        .map(|g| g.anyone().count_ones() as i64)
+        .sum()
 }
 
 fn day06b(contents: &str) -> i64 {
-    let groups = split_groups(contents);
-    let answersets = groups.into_iter().map(|g| make_set_intersection(&g));
-    let counts = answersets.map(|set| set.len());
-    let sum: usize = counts.sum();
-    sum as i64
+    analyze_groups(contents)
+        .iter()
+        .map(|g| g.everyone().count_ones() as i64)
+        .sum()
 }
 
 pub fn day06(contents: &str, part: char) -> i64 {
@@ -99,4 +210,43 @@ mod tests {
         let ansb = day06::day06b(newinput);
         assert_eq!(ansb, 6);
     }
+
+    #[test]
+    fn test_group_answers() {
+        let input = "abc
+
+        a
+        b
+        c
+
+        ab
+        ac
+
+        a
+        a
+        a
+        a
+
+        b";
+
+        let groups = day06::analyze_groups(input);
+        for (group, g) in day06::split_groups(input).iter().zip(&groups) {
+            assert_eq!(day06::bits_to_set(g.anyone()), day06::make_set_union(group));
+            assert_eq!(
+                day06::bits_to_set(g.everyone()),
+                day06::make_set_intersection(group)
+            );
+        }
+
+        assert_eq!(groups[2].num_people(), 2);
+        assert_eq!(groups[2].count('a'), 2);
+        assert_eq!(groups[2].count('b'), 1);
+        assert_eq!(groups[3].count('a'), 4);
+        assert_eq!(day06::bits_to_set(groups[2].exactly_one()).len(), 2);
+        assert_eq!(groups[3].exactly_one(), 0);
+
+        assert_eq!(day06::quorum_sum(input, 1), 11);
+        assert_eq!(day06::quorum_sum(input, 2), 2);
+        assert_eq!(day06::quorum_sum(input, 5), 0);
+    }
 }
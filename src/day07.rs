@@ -1,4 +1,5 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 use regex::Regex;
 
@@ -30,52 +31,259 @@ fn parse_rule(line: &str) -> Rule {
     }
 }
 
-fn find_ancestors(rules: &[Rule], color: String) -> HashSet<String> {
-    let mut parents: Vec<String> = Vec::new();
-    for rule in rules {
-        let has = rule.contains.get(&color);
-        if has.is_some() {
-            parents.push(rule.color.to_string());
+/// Bag containment rules as an indexed graph. Edges point from a bag to the
+/// bags it directly contains, weighted by quantity.
+#[derive(Debug)]
+struct BagGraph {
+    colors: Vec<String>,
+    index: HashMap<String, usize>,
+    children: Vec<Vec<(usize, i64)>>,
+    parents: Vec<Vec<usize>>,
+    order: Vec<usize>,
+    position: Vec<usize>,
+    ancestor_memo: HashMap<usize, HashSet<usize>>,
+    descendant_memo: HashMap<usize, i64>,
+}
+
+#[derive(Debug, PartialEq)]
+enum BagGraphError {
+    Cycle(Vec<String>),
+    UnknownBag(String),
+}
+
+impl fmt::Display for BagGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BagGraphError::Cycle(colors) => {
+                write!(f, "Bag rules contain a cycle: {}", colors.join(" -> "))
+            }
+            BagGraphError::UnknownBag(color) => write!(f, "No rule mentions bag {:?}", color),
         }
     }
-    let mut allancestors: HashSet<String> = HashSet::new();
-    for parent in parents {
-        allancestors.insert(parent.clone());
-        let ancestors = find_ancestors(rules, parent);
-        for ancestor in ancestors {
-            allancestors.insert(ancestor);
+}
+
+impl std::error::Error for BagGraphError {}
+
+impl BagGraph {
+    fn new(rules: &[Rule]) -> Result<BagGraph, BagGraphError> {
+        let mut colors: Vec<String> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut intern = |color: &str| -> usize {
+            if let Some(i) = index.get(color) {
+                return *i;
+            }
+            colors.push(color.to_string());
+            index.insert(color.to_string(), colors.len() - 1);
+            colors.len() - 1
+        };
+
+        let mut edges = Vec::new();
+        for rule in rules {
+            let parent = intern(&rule.color);
+            let mut contents: Vec<(&String, &i64)> = rule.contains.iter().collect();
+            contents.sort();
+            for (color, amount) in contents {
+                edges.push((parent, intern(color), *amount));
+            }
+        }
+
+        let mut children = vec![Vec::new(); colors.len()];
+        let mut parents = vec![Vec::new(); colors.len()];
+        for (parent, child, amount) in edges {
+            children[parent].push((child, amount));
+            parents[child].push(parent);
         }
+
+        let mut graph = BagGraph {
+            colors,
+            index,
+            children,
+            parents,
+            order: vec![],
+            position: vec![],
+            ancestor_memo: HashMap::new(),
+            descendant_memo: HashMap::new(),
+        };
+        graph.order = graph.sort_topologically()?;
+        graph.position = vec![0; graph.order.len()];
+        for (pos, node) in graph.order.iter().enumerate() {
+            graph.position[*node] = pos;
+        }
+        Ok(graph)
     }
-    allancestors
-}
 
-fn get_num_descendents(rules: &HashMap<String, HashMap<String, i64>>, color: &str) -> i64 {
-    let rule = rules.get(color).unwrap();
-    if rule.keys().len() == 0 {
-        1
-    } else {
-        let mut sum = 0;
-        for (k, v) in rule {
-            let d = get_num_descendents(rules, k);
-            sum += v * d;
+    fn node(&self, color: &str) -> Result<usize, BagGraphError> {
+        self.index
+            .get(color)
+            .copied()
+            .ok_or_else(|| BagGraphError::UnknownBag(color.to_string()))
+    }
+
+    /// Kahn's algorithm, outermost bags first. If some bags are never freed
+    /// up, they sit on a cycle and the error names one.
+    fn sort_topologically(&self) -> Result<Vec<usize>, BagGraphError> {
+        let mut indegree: Vec<usize> = self.parents.iter().map(|p| p.len()).collect();
+        let mut ready: Vec<usize> = (0..self.colors.len())
+            .filter(|n| indegree[*n] == 0)
+            .rev()
+            .collect();
+        let mut order = Vec::new();
+        while let Some(node) = ready.pop() {
+            order.push(node);
+            for (child, _) in &self.children[node] {
+                indegree[*child] -= 1;
+                if indegree[*child] == 0 {
+                    ready.push(*child);
+                }
+            }
+        }
+
+        if order.len() == self.colors.len() {
+            Ok(order)
+        } else {
+            // Bags left over can also hang below a cycle without being on
+            // one, so search from each until a cycle turns up.
+            let cycle = (0..self.colors.len())
+                .filter(|n| indegree[*n] > 0)
+                .find_map(|n| self.find_cycle(n))
+                .unwrap();
+            Err(BagGraphError::Cycle(
+                cycle.iter().map(|n| self.colors[*n].clone()).collect(),
+            ))
+        }
+    }
+
+    /// Iterative depth-first search from `start` for a path that comes back
+    /// on itself. The returned cycle starts and ends with the same bag.
+    fn find_cycle(&self, start: usize) -> Option<Vec<usize>> {
+        let mut on_path = vec![false; self.colors.len()];
+        let mut done = vec![false; self.colors.len()];
+        let mut path: Vec<usize> = vec![start];
+        let mut next_child: Vec<usize> = vec![0];
+        on_path[start] = true;
+
+        while let Some(node) = path.last().copied() {
+            let i = next_child.last_mut().unwrap();
+            if let Some((child, _)) = self.children[node].get(*i) {
+                *i += 1;
+                if on_path[*child] {
+                    let from = path.iter().position(|n| n == child).unwrap();
+                    let mut cycle = path[from..].to_vec();
+                    cycle.push(*child);
+                    return Some(cycle);
+                }
+                if !done[*child] {
+                    on_path[*child] = true;
+                    path.push(*child);
+                    next_child.push(0);
+                }
+            } else {
+                on_path[node] = false;
+                done[node] = true;
+                path.pop();
+                next_child.pop();
+            }
+        }
+        None
+    }
+
+    fn topological_order(&self) -> Vec<&str> {
+        self.order
+            .iter()
+            .map(|n| self.colors[*n].as_str())
+            .collect()
+    }
+
+    /// Graphviz DOT rendering of the containment graph. Edges are labelled
+    /// with quantities and `highlight` is drawn filled.
+    fn to_dot(&self, highlight: &str) -> String {
+        let mut dot = String::from("digraph bags {\n");
+        for color in self.topological_order() {
+            if color == highlight {
+                dot.push_str(&format!(
+                    "    {:?} [style=filled, fillcolor=gold];\n",
+                    color
+                ));
+            } else {
+                dot.push_str(&format!("    {:?};\n", color));
+            }
+        }
+        for node in &self.order {
+            for (child, amount) in &self.children[*node] {
+                dot.push_str(&format!(
+                    "    {:?} -> {:?} [label=\"{}\"];\n",
+                    self.colors[*node], self.colors[*child], amount
+                ));
+            }
         }
-        sum + 1
+        dot.push_str("}\n");
+        dot
     }
+
+    /// All bags that can eventually contain `color`. Every container comes
+    /// before its contents in topological order, so filling the memo in that
+    /// order only ever looks up parents that are already done.
+    fn ancestors(&mut self, color: &str) -> Result<HashSet<String>, BagGraphError> {
+        let target = self.node(color)?;
+        for pos in 0..=self.position[target] {
+            let node = self.order[pos];
+            if self.ancestor_memo.contains_key(&node) {
+                continue;
+            }
+            let mut ancestors = HashSet::new();
+            for parent in &self.parents[node] {
+                ancestors.insert(*parent);
+                ancestors.extend(self.ancestor_memo[parent].iter());
+            }
+            self.ancestor_memo.insert(node, ancestors);
+        }
+        Ok(self.ancestor_memo[&target]
+            .iter()
+            .map(|n| self.colors[*n].clone())
+            .collect())
+    }
+
+    /// Total number of bags inside `color`, not counting `color` itself.
+    /// Filled in reverse topological order so contents are counted first.
+    fn count_descendants(&mut self, color: &str) -> Result<i64, BagGraphError> {
+        let target = self.node(color)?;
+        for pos in (self.position[target]..self.order.len()).rev() {
+            let node = self.order[pos];
+            if self.descendant_memo.contains_key(&node) {
+                continue;
+            }
+            let count = self.children[node]
+                .iter()
+                .map(|(child, amount)| amount * (1 + self.descendant_memo[child]))
+                .sum();
+            self.descendant_memo.insert(node, count);
+        }
+        Ok(self.descendant_memo[&target])
+    }
+}
+
+fn make_graph(lines: &[String]) -> Result<BagGraph, BagGraphError> {
+    let rules: Vec<Rule> = lines
+        .iter()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| parse_rule(x))
+        .collect();
+    BagGraph::new(&rules)
 }
 
 fn day07a(lines: &[String]) -> i64 {
-    let rules: Vec<Rule> = lines.iter().map(|x| parse_rule(x)).collect();
-    let allancestors = find_ancestors(&rules, "shiny gold".to_string());
-    allancestors.len() as i64
+    let mut graph = make_graph(lines).unwrap();
+    graph.ancestors("shiny gold").unwrap().len() as i64
 }
 
 fn day07b(lines: &[String]) -> i64 {
-    let rules: Vec<Rule> = lines.iter().map(|x| parse_rule(x)).collect();
-    let mut rulesmap = HashMap::new();
-    for rule in rules {
-        rulesmap.insert(rule.color, rule.contains);
-    }
-    get_num_descendents(&rulesmap, "shiny gold") - 1
+    let mut graph = make_graph(lines).unwrap();
+    graph.count_descendants("shiny gold").unwrap()
+}
+
+pub fn day07_dot(lines: &[String]) -> String {
+    let graph = make_graph(lines).unwrap();
+    graph.to_dot("shiny gold")
 }
 
 pub fn day07(lines: &[String], part: char) -> i64 {
@@ -115,4 +323,83 @@ mod tests {
         let lines: Vec<String> = input2.split('\n').map(|x| x.to_string()).collect();
         assert_eq!(day07::day07b(&lines), 126);
     }
+
+    #[test]
+    fn test_dot() {
+        let input = "bright white bags contain 1 shiny gold bag.
+        shiny gold bags contain 2 faded blue bags.
+        faded blue bags contain no other bags.";
+        let lines: Vec<String> = input.split('\n').map(|x| x.to_string()).collect();
+        assert_eq!(
+            day07::day07_dot(&lines),
+            "digraph bags {
+    \"bright white\";
+    \"shiny gold\" [style=filled, fillcolor=gold];
+    \"faded blue\";
+    \"bright white\" -> \"shiny gold\" [label=\"1\"];
+    \"shiny gold\" -> \"faded blue\" [label=\"2\"];
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_bag_graph() {
+        let input = "light red bags contain 1 bright white bag, 2 muted yellow bags.
+        bright white bags contain 1 shiny gold bag.
+        muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+        shiny gold bags contain 1 dark olive bag.
+        dark olive bags contain 3 faded blue bags.
+        faded blue bags contain no other bags.";
+        let lines: Vec<String> = input.split('\n').map(|x| x.to_string()).collect();
+        let mut graph = day07::make_graph(&lines).unwrap();
+
+        let order = graph.topological_order();
+        let pos = |c: &str| order.iter().position(|x| *x == c).unwrap();
+        assert_eq!(order.len(), 6);
+        assert!(pos("light red") < pos("bright white"));
+        assert!(pos("muted yellow") < pos("shiny gold"));
+        assert!(pos("dark olive") < pos("faded blue"));
+
+        let ancestors = graph.ancestors("shiny gold").unwrap();
+        assert_eq!(ancestors.len(), 3);
+        assert!(ancestors.contains("light red"));
+        assert_eq!(graph.ancestors("light red").unwrap().len(), 0);
+        assert_eq!(graph.count_descendants("dark olive").unwrap(), 3);
+        assert_eq!(graph.count_descendants("shiny gold").unwrap(), 4);
+        assert_eq!(
+            graph.count_descendants("plaid"),
+            Err(day07::BagGraphError::UnknownBag("plaid".to_string()))
+        );
+
+        let cyclic = "shiny gold bags contain 2 dark red bags.
+        dark red bags contain 1 dark blue bag, 3 faded blue bags.
+        dark blue bags contain 1 shiny gold bag.
+        faded blue bags contain no other bags.";
+        let lines: Vec<String> = cyclic.split('\n').map(|x| x.to_string()).collect();
+        let err = day07::make_graph(&lines).unwrap_err();
+        assert_eq!(
+            err,
+            day07::BagGraphError::Cycle(vec![
+                "shiny gold".to_string(),
+                "dark red".to_string(),
+                "dark blue".to_string(),
+                "shiny gold".to_string(),
+            ])
+        );
+        assert_eq!(
+            err.to_string(),
+            "Bag rules contain a cycle: shiny gold -> dark red -> dark blue -> shiny gold"
+        );
+
+        // light red is stuck behind the cycle but not on it.
+        let downstream = "light red bags contain no other bags.
+        dark red bags contain 1 dark blue bag, 1 light red bag.
+        dark blue bags contain 1 dark red bag.";
+        let lines: Vec<String> = downstream.split('\n').map(|x| x.to_string()).collect();
+        assert_eq!(
+            day07::make_graph(&lines).unwrap_err().to_string(),
+            "Bag rules contain a cycle: dark red -> dark blue -> dark red"
+        );
+    }
 }
@@ -1,9 +1,45 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::ops::Range;
+
+/// Opcodes of the console. `acc`, `jmp` and `nop` are the puzzle's own,
+/// the rest take register operands (register 0 is the accumulator).
 #[derive(Debug, PartialEq, Eq, Clone)]
 enum Op {
     ACC,
     JMP,
     NOP,
+    Mul(usize),
+    Set(usize),
+    Cpy(usize, usize),
+    Jz(usize),
+    Jnz(usize),
+    Out(usize),
+}
+
+impl Op {
+    fn registers(&self) -> Vec<usize> {
+        match self {
+            Op::ACC => vec![0],
+            Op::JMP | Op::NOP => vec![],
+            Op::Mul(r) | Op::Set(r) | Op::Jz(r) | Op::Jnz(r) | Op::Out(r) => vec![*r],
+            Op::Cpy(from, to) => vec![*from, *to],
+        }
+    }
+
+    /// Whether `arg` is a jump offset (or, for `nop`, would be one if the
+    /// instruction were swapped for a `jmp`).
+    fn is_jump(&self) -> bool {
+        matches!(self, Op::JMP | Op::NOP | Op::Jz(_) | Op::Jnz(_))
+    }
+
+    /// Whether the instruction uses `arg` at all.
+    fn takes_arg(&self) -> bool {
+        !matches!(self, Op::Cpy(_, _) | Op::Out(_))
+    }
 }
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct Inst {
     op: Op,
@@ -11,38 +47,214 @@ struct Inst {
     runcount: i64,
 }
 
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.op.takes_arg() {
+            write!(f, "{} {:+}", self.op, self.arg)
+        } else {
+            write!(f, "{}", self.op)
+        }
+    }
+}
+
+/// How `VM::run` decides that a program will never halt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LoopCheck {
+    /// Never; rely on `max_steps` instead.
+    #[allow(dead_code)]
+    Off,
+    /// An instruction is about to run a second time. This is the puzzle's
+    /// rule, and only exact for programs without conditional jumps.
+    Revisit,
+    /// The instruction pointer and registers are the same as at some earlier
+    /// step. Exact for any program, at the cost of remembering every state.
+    #[allow(dead_code)]
+    State,
+}
+
+#[derive(Debug, Clone)]
+struct VmConfig {
+    registers: usize,
+    max_steps: Option<usize>,
+    loop_check: LoopCheck,
+}
+
+impl Default for VmConfig {
+    fn default() -> VmConfig {
+        VmConfig {
+            registers: 1,
+            max_steps: None,
+            loop_check: LoopCheck::Revisit,
+        }
+    }
+}
+
+/// Why the VM stopped running.
+#[derive(Debug, Clone, PartialEq)]
+enum Halt {
+    /// The instruction pointer landed just past the last instruction.
+    Terminated,
+    LoopDetected,
+    StepLimit,
+    InvalidIp,
+    InvalidRegister(usize),
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Halt::Terminated => write!(f, "program terminated"),
+            Halt::LoopDetected => write!(f, "loop detected"),
+            Halt::StepLimit => write!(f, "step limit reached"),
+            Halt::InvalidIp => write!(f, "instruction pointer out of bounds"),
+            Halt::InvalidRegister(r) => write!(f, "no register r{}", r),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct VM {
     instructions: Vec<Inst>,
     ip: i64,
-    accumulator: i64,
+    registers: Vec<i64>,
+    output: Vec<i64>,
+    steps: usize,
+    config: VmConfig,
+    seen: HashSet<(i64, Vec<i64>)>,
 }
 
 impl VM {
-    fn step(&mut self) {
+    fn new(instructions: Vec<Inst>, config: VmConfig) -> VM {
+        VM {
+            instructions,
+            ip: 0,
+            registers: vec![0; config.registers.max(1)],
+            output: vec![],
+            steps: 0,
+            config,
+            seen: HashSet::new(),
+        }
+    }
+
+    fn accumulator(&self) -> i64 {
+        self.registers[0]
+    }
+
+    /// Runs the instruction at `ip`, or says why it can't.
+    fn step(&mut self) -> Result<(), Halt> {
+        let len = self.instructions.len() as i64;
+        if self.ip == len {
+            return Err(Halt::Terminated);
+        } else if self.ip < 0 || self.ip > len {
+            return Err(Halt::InvalidIp);
+        }
+        if matches!(self.config.max_steps, Some(max) if self.steps >= max) {
+            return Err(Halt::StepLimit);
+        }
+        let nregs = self.registers.len();
         let inst = self.instructions.get_mut(self.ip as usize).unwrap();
+        if let Some(r) = inst.op.registers().into_iter().find(|r| *r >= nregs) {
+            return Err(Halt::InvalidRegister(r));
+        }
         inst.runcount += 1;
+        self.steps += 1;
 
+        let regs = &mut self.registers;
+        let mut next = self.ip + 1;
         match inst.op {
-            Op::ACC => self.accumulator += inst.arg,
-            Op::JMP => self.ip += inst.arg,
+            Op::ACC => regs[0] = regs[0].wrapping_add(inst.arg),
+            Op::JMP => next = self.ip + inst.arg,
             Op::NOP => {}
+            Op::Mul(r) => regs[r] = regs[r].wrapping_mul(inst.arg),
+            Op::Set(r) => regs[r] = inst.arg,
+            Op::Cpy(from, to) => regs[to] = regs[from],
+            Op::Jz(r) if regs[r] == 0 => next = self.ip + inst.arg,
+            Op::Jnz(r) if regs[r] != 0 => next = self.ip + inst.arg,
+            Op::Jz(_) | Op::Jnz(_) => {}
+            Op::Out(r) => self.output.push(regs[r]),
         };
+        self.ip = next;
+        Ok(())
+    }
 
-        if inst.op != Op::JMP {
-            self.ip += 1;
-        };
+    /// Whether the configured loop check says the next step repeats itself.
+    fn looping(&mut self) -> bool {
+        match self.config.loop_check {
+            LoopCheck::Off => false,
+            LoopCheck::Revisit => matches!(
+                self.instructions.get(self.ip as usize),
+                Some(inst) if inst.runcount > 0
+            ),
+            LoopCheck::State => !self.seen.insert((self.ip, self.registers.clone())),
+        }
+    }
+
+    fn run(&mut self) -> Halt {
+        loop {
+            if self.looping() {
+                return Halt::LoopDetected;
+            }
+            if let Err(halt) = self.step() {
+                return halt;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::ACC => write!(f, "acc"),
+            Op::JMP => write!(f, "jmp"),
+            Op::NOP => write!(f, "nop"),
+            Op::Mul(r) => write!(f, "mul r{}", r),
+            Op::Set(r) => write!(f, "set r{}", r),
+            Op::Cpy(from, to) => write!(f, "cpy r{} r{}", from, to),
+            Op::Jz(r) => write!(f, "jz r{}", r),
+            Op::Jnz(r) => write!(f, "jnz r{}", r),
+            Op::Out(r) => write!(f, "out r{}", r),
+        }
+    }
+}
+
+/// Number of register operands each mnemonic takes.
+fn register_count(mnemonic: &str) -> Option<usize> {
+    match mnemonic {
+        "acc" | "jmp" | "nop" => Some(0),
+        "mul" | "set" | "jz" | "jnz" | "out" => Some(1),
+        "cpy" => Some(2),
+        _ => None,
+    }
+}
+
+fn parse_op(mnemonic: &str, regs: &[usize]) -> Option<Op> {
+    let op = match (mnemonic, regs) {
+        ("acc", []) => Op::ACC,
+        ("jmp", []) => Op::JMP,
+        ("nop", []) => Op::NOP,
+        ("mul", [r]) => Op::Mul(*r),
+        ("set", [r]) => Op::Set(*r),
+        ("cpy", [from, to]) => Op::Cpy(*from, *to),
+        ("jz", [r]) => Op::Jz(*r),
+        ("jnz", [r]) => Op::Jnz(*r),
+        ("out", [r]) => Op::Out(*r),
+        _ => return None,
+    };
+    Some(op)
+}
+
+fn parse_register(s: &str) -> Option<usize> {
+    let n = s.strip_prefix('r')?;
+    if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) {
+        n.parse().ok()
+    } else {
+        None
     }
 }
 
 fn parse_line(line: &str) -> Result<Inst, Box<dyn std::error::Error>> {
     let parts: Vec<&str> = line.trim().split(' ').collect();
-    let op = match parts[0] {
-        "acc" => Op::ACC,
-        "jmp" => Op::JMP,
-        "nop" => Op::NOP,
-        _ => panic!("Didn't recognize op code"),
-    };
+    let op = parse_op(parts[0], &[]).expect("Didn't recognize op code");
     let arg = parts[1].parse::<i64>()?;
     Ok(Inst {
         op,
@@ -51,20 +263,780 @@ fn parse_line(line: &str) -> Result<Inst, Box<dyn std::error::Error>> {
     })
 }
 
-fn day08a(lines: &[String]) -> i64 {
-    let instructions: Vec<Inst> = lines.iter().map(|line| parse_line(line).unwrap()).collect();
-    let mut vm = VM {
-        instructions,
-        ip: 0,
-        accumulator: 0,
+/// An assembler error, located by 1-based line number and the byte range of
+/// the offending text within that line.
+#[derive(Debug, PartialEq)]
+struct AsmError {
+    line: usize,
+    span: Range<usize>,
+    message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, columns {}-{}: {}",
+            self.line,
+            self.span.start + 1,
+            self.span.end,
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug)]
+enum Operand<'a> {
+    Offset(i64),
+    Label(&'a str, Range<usize>),
+}
+
+/// Splits a line into whitespace separated tokens, each paired with its byte
+/// range. Anything after a `;` is a comment.
+fn tokenize(line: &str) -> Vec<(&str, Range<usize>)> {
+    let code = match line.find(';') {
+        Some(idx) => &line[..idx],
+        None => line,
     };
-    loop {
-        if vm.instructions.get(vm.ip as usize).unwrap().runcount > 0 {
-            break;
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in code.char_indices() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(st)) => {
+                tokens.push((&code[st..i], st..i));
+                start = None;
+            }
+            _ => {}
         }
-        vm.step();
     }
-    vm.accumulator
+    if let Some(st) = start {
+        tokens.push((&code[st..], st..code.len()));
+    }
+    tokens
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Assembles handheld console source. Each line holds an optional `label:`,
+/// an optional instruction and an optional `; comment`. Registers are
+/// written `r0`, `r1`, ... and come first, e.g. `jnz r1 loop` or
+/// `cpy r0 r2`. Jumps (and `nop`) take either a label or a signed relative
+/// offset, `acc`, `mul` and `set` take a signed number, and `cpy` and `out`
+/// take nothing else. A label on a line of its own names the next
+/// instruction, or the end of the program if nothing follows it.
+fn assemble(source: &str) -> Result<Vec<Inst>, AsmError> {
+    let err = |line: usize, span: Range<usize>, message: String| AsmError {
+        line: line + 1,
+        span,
+        message,
+    };
+
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut pending: Vec<(usize, Op, Operand)> = Vec::new();
+    for (linenum, line) in source.lines().enumerate() {
+        let mut tokens = tokenize(line).into_iter().peekable();
+        if let Some((tok, span)) = tokens.peek().cloned() {
+            if let Some(name) = tok.strip_suffix(':') {
+                if !is_label(name) {
+                    return Err(err(linenum, span, format!("invalid label `{}`", name)));
+                }
+                if labels.insert(name, pending.len()).is_some() {
+                    return Err(err(linenum, span, format!("duplicate label `{}`", name)));
+                }
+                tokens.next();
+            }
+        }
+
+        let (optok, opspan) = match tokens.next() {
+            Some(t) => t,
+            None => continue,
+        };
+        let nregs = register_count(optok).ok_or_else(|| {
+            err(
+                linenum,
+                opspan.clone(),
+                format!("unknown opcode `{}`", optok),
+            )
+        })?;
+        let mut regs = Vec::new();
+        for _ in 0..nregs {
+            let (regtok, regspan) = tokens.next().ok_or_else(|| {
+                err(
+                    linenum,
+                    opspan.clone(),
+                    format!("`{}` needs a register", optok),
+                )
+            })?;
+            let reg = parse_register(regtok)
+                .ok_or_else(|| err(linenum, regspan, format!("invalid register `{}`", regtok)))?;
+            regs.push(reg);
+        }
+        let op = parse_op(optok, &regs).unwrap();
+
+        let operand = if op.takes_arg() {
+            let (argtok, argspan) = tokens
+                .next()
+                .ok_or_else(|| err(linenum, opspan, format!("`{}` needs an operand", op)))?;
+            match argtok.parse::<i64>() {
+                Ok(n) => Operand::Offset(n),
+                Err(_) if op.is_jump() && is_label(argtok) => Operand::Label(argtok, argspan),
+                Err(_) => {
+                    return Err(err(
+                        linenum,
+                        argspan,
+                        format!("invalid operand `{}` for `{}`", argtok, op),
+                    ))
+                }
+            }
+        } else {
+            Operand::Offset(0)
+        };
+        if let Some((extra, span)) = tokens.next() {
+            return Err(err(linenum, span, format!("unexpected `{}`", extra)));
+        }
+        pending.push((linenum, op, operand));
+    }
+
+    let mut instructions = Vec::new();
+    for (addr, (linenum, op, operand)) in pending.into_iter().enumerate() {
+        let arg = match operand {
+            Operand::Offset(n) => n,
+            Operand::Label(name, span) => match labels.get(name) {
+                Some(target) => *target as i64 - addr as i64,
+                None => return Err(err(linenum, span, format!("undefined label `{}`", name))),
+            },
+        };
+        instructions.push(Inst {
+            op,
+            arg,
+            runcount: 0,
+        });
+    }
+    Ok(instructions)
+}
+
+/// Turns instructions back into canonical assembler text. Every jump or
+/// `nop` whose target lies inside the program (or just past its end) gets a
+/// label named after the absolute address, e.g. `L12`. Targets further out
+/// stay as relative offsets.
+#[allow(dead_code)]
+fn disassemble(instructions: &[Inst]) -> String {
+    let target = |addr: usize, inst: &Inst| -> Option<usize> {
+        let t = addr as i64 + inst.arg;
+        if inst.op.is_jump() && t >= 0 && t <= instructions.len() as i64 {
+            Some(t as usize)
+        } else {
+            None
+        }
+    };
+    let labelled: BTreeSet<usize> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(addr, inst)| target(addr, inst))
+        .collect();
+
+    let mut text = String::new();
+    for (addr, inst) in instructions.iter().enumerate() {
+        if labelled.contains(&addr) {
+            text.push_str(&format!("L{}:\n", addr));
+        }
+        match target(addr, inst) {
+            Some(t) => text.push_str(&format!("    {} L{}\n", inst.op, t)),
+            None => text.push_str(&format!("    {}\n", inst)),
+        }
+    }
+    if labelled.contains(&instructions.len()) {
+        text.push_str(&format!("L{}:\n", instructions.len()));
+    }
+    text
+}
+
+/// A value read from the VM state: the accumulator, another register, the
+/// instruction pointer, or how many times the instruction at an address
+/// has run.
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Acc,
+    Reg(usize),
+    Ip,
+    Runs(usize),
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Acc => write!(f, "acc"),
+            Expr::Reg(r) => write!(f, "r{}", r),
+            Expr::Ip => write!(f, "ip"),
+            Expr::Runs(addr) => write!(f, "runs[{}]", addr),
+        }
+    }
+}
+
+fn parse_expr(s: &str) -> Option<Expr> {
+    match s {
+        "acc" => Some(Expr::Acc),
+        "ip" => Some(Expr::Ip),
+        _ if parse_register(s).is_some() => parse_register(s).map(Expr::Reg),
+        _ => {
+            let addr = s.strip_prefix("runs[")?.strip_suffix(']')?;
+            addr.parse().ok().map(Expr::Runs)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cmp {
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+}
+
+impl Cmp {
+    fn parse(s: &str) -> Option<Cmp> {
+        match s {
+            "<" => Some(Cmp::Lt),
+            "<=" => Some(Cmp::Le),
+            "==" => Some(Cmp::Eq),
+            "!=" => Some(Cmp::Ne),
+            ">=" => Some(Cmp::Ge),
+            ">" => Some(Cmp::Gt),
+            _ => None,
+        }
+    }
+
+    fn holds(self, lhs: i64, rhs: i64) -> bool {
+        match self {
+            Cmp::Lt => lhs < rhs,
+            Cmp::Le => lhs <= rhs,
+            Cmp::Eq => lhs == rhs,
+            Cmp::Ne => lhs != rhs,
+            Cmp::Ge => lhs >= rhs,
+            Cmp::Gt => lhs > rhs,
+        }
+    }
+}
+
+impl fmt::Display for Cmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Eq => "==",
+            Cmp::Ne => "!=",
+            Cmp::Ge => ">=",
+            Cmp::Gt => ">",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Breakpoint {
+    Address(usize),
+    Condition(Expr, Cmp, i64),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Address(addr) => write!(f, "address {}", addr),
+            Breakpoint::Condition(expr, cmp, n) => write!(f, "{} {} {}", expr, cmp, n),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    Halt(Halt),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Stepped => write!(f, "stepped"),
+            Stop::Breakpoint(n) => write!(f, "hit breakpoint {}", n),
+            Stop::Halt(halt) => write!(f, "{}", halt),
+        }
+    }
+}
+
+const DEBUGGER_HELP: &str = "commands:
+  step [n]               run n instructions (default 1)
+  continue               run until a breakpoint, termination or a loop
+  back [n]               rewind n instructions (default 1)
+  break <addr>           stop before the instruction at addr
+  break <expr> <op> <n>  stop when a condition holds, e.g. break acc > 100
+  delete <n>             remove breakpoint n
+  watch <expr>           show expr at every stop (acc, r1, ip or runs[addr])
+  print <expr>           show expr once
+  patch <addr> <inst>    replace an instruction, e.g. patch 7 nop -4
+  output                 show everything written by `out` so far
+  list                   show breakpoints, watches and the program
+  quit
+";
+
+/// A VM state to rewind to: instruction pointer, registers and how much
+/// output had been written.
+type Snapshot = (i64, Vec<i64>, usize);
+
+/// Wraps a `VM` with breakpoints, watch expressions and an execution
+/// history that steps can be rewound through.
+#[derive(Debug)]
+struct Debugger {
+    vm: VM,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<Expr>,
+    history: Vec<Snapshot>,
+}
+
+impl Debugger {
+    fn new(instructions: Vec<Inst>, config: VmConfig) -> Debugger {
+        Debugger {
+            vm: VM::new(instructions, config),
+            breakpoints: vec![],
+            watches: vec![],
+            history: vec![],
+        }
+    }
+
+    fn eval(&self, expr: &Expr) -> i64 {
+        match expr {
+            Expr::Acc => self.vm.accumulator(),
+            Expr::Reg(r) => self.vm.registers.get(*r).copied().unwrap_or(0),
+            Expr::Ip => self.vm.ip,
+            Expr::Runs(addr) => self
+                .vm
+                .instructions
+                .get(*addr)
+                .map(|inst| inst.runcount)
+                .unwrap_or(0),
+        }
+    }
+
+    fn step(&mut self) -> Stop {
+        let snapshot = (self.vm.ip, self.vm.registers.clone(), self.vm.output.len());
+        match self.vm.step() {
+            Ok(()) => {
+                self.history.push(snapshot);
+                Stop::Stepped
+            }
+            Err(halt) => Stop::Halt(halt),
+        }
+    }
+
+    fn cont(&mut self) -> Stop {
+        let stop = self.step();
+        if stop != Stop::Stepped {
+            return stop;
+        }
+        loop {
+            if let Some(n) = self.breakpoints.iter().position(|b| match b {
+                Breakpoint::Address(addr) => self.vm.ip == *addr as i64,
+                Breakpoint::Condition(expr, cmp, n) => cmp.holds(self.eval(expr), *n),
+            }) {
+                return Stop::Breakpoint(n);
+            }
+            if self.vm.looping() {
+                return Stop::Halt(Halt::LoopDetected);
+            }
+            let stop = self.step();
+            if stop != Stop::Stepped {
+                return stop;
+            }
+        }
+    }
+
+    /// Undoes up to `n` steps, returning how many were actually undone.
+    fn rewind(&mut self, n: usize) -> usize {
+        let mut undone = 0;
+        while undone < n {
+            match self.history.pop() {
+                Some((ip, registers, outlen)) => {
+                    self.vm.ip = ip;
+                    self.vm.registers = registers;
+                    self.vm.output.truncate(outlen);
+                    self.vm.instructions[ip as usize].runcount -= 1;
+                    self.vm.steps -= 1;
+                    undone += 1;
+                }
+                None => break,
+            }
+        }
+        // Remembered states may lie in the undone future now.
+        self.vm.seen.clear();
+        undone
+    }
+
+    fn status(&self) -> String {
+        let mut out = format!("ip = {}, acc = {}", self.vm.ip, self.vm.accumulator());
+        for (r, val) in self.vm.registers.iter().enumerate().skip(1) {
+            out.push_str(&format!(", r{} = {}", r, val));
+        }
+        match self.vm.instructions.get(self.vm.ip as usize) {
+            Some(inst) if self.vm.ip >= 0 => out.push_str(&format!(", next: {}\n", inst)),
+            _ => out.push('\n'),
+        }
+        for watch in &self.watches {
+            out.push_str(&format!("  {} = {}\n", watch, self.eval(watch)));
+        }
+        out
+    }
+
+    /// Runs one line of debugger input, returning the text to show the user
+    /// or `None` when the session should end.
+    fn execute(&mut self, command: &str) -> Option<String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let count = |i: usize| -> Result<usize, String> {
+            match words.get(i) {
+                Some(w) => w.parse().map_err(|_| format!("not a count: {}\n", w)),
+                None => Ok(1),
+            }
+        };
+        let out = match words.as_slice() {
+            [] => Ok(String::new()),
+            ["quit"] | ["q"] => return None,
+            ["help"] | ["h"] => Ok(DEBUGGER_HELP.to_string()),
+            ["step", ..] | ["s", ..] => count(1).map(|n| {
+                let mut stop = Stop::Stepped;
+                for _ in 0..n {
+                    stop = self.step();
+                    if stop != Stop::Stepped {
+                        break;
+                    }
+                }
+                format!("{}\n{}", stop, self.status())
+            }),
+            ["continue"] | ["c"] => {
+                let stop = self.cont();
+                Ok(format!("{}\n{}", stop, self.status()))
+            }
+            ["back", ..] | ["b", ..] => count(1).map(|n| {
+                let undone = self.rewind(n);
+                format!("rewound {} steps\n{}", undone, self.status())
+            }),
+            ["break", addr] => match addr.parse() {
+                Ok(addr) => {
+                    self.breakpoints.push(Breakpoint::Address(addr));
+                    Ok(format!(
+                        "breakpoint {} at address {}\n",
+                        self.breakpoints.len() - 1,
+                        addr
+                    ))
+                }
+                Err(_) => Err(format!("not an address: {}\n", addr)),
+            },
+            ["break", expr, cmp, n] => match (parse_expr(expr), Cmp::parse(cmp), n.parse()) {
+                (Some(expr), Some(cmp), Ok(n)) => {
+                    let b = Breakpoint::Condition(expr, cmp, n);
+                    let out = format!("breakpoint {} when {}\n", self.breakpoints.len(), b);
+                    self.breakpoints.push(b);
+                    Ok(out)
+                }
+                _ => Err(format!("bad condition: {} {} {}\n", expr, cmp, n)),
+            },
+            ["delete", n] => match n.parse::<usize>() {
+                Ok(n) if n < self.breakpoints.len() => {
+                    let b = self.breakpoints.remove(n);
+                    Ok(format!("deleted breakpoint {} ({})\n", n, b))
+                }
+                _ => Err(format!("no breakpoint {}\n", n)),
+            },
+            ["watch", expr] => match parse_expr(expr) {
+                Some(expr) => {
+                    let out = format!("watching {} = {}\n", expr, self.eval(&expr));
+                    self.watches.push(expr);
+                    Ok(out)
+                }
+                None => Err(format!("bad expression: {}\n", expr)),
+            },
+            ["print", expr] | ["p", expr] => match parse_expr(expr) {
+                Some(expr) => Ok(format!("{} = {}\n", expr, self.eval(&expr))),
+                None => Err(format!("bad expression: {}\n", expr)),
+            },
+            ["patch", addr, inst @ ..] => {
+                let inst = inst.join(" ");
+                match (addr.parse::<usize>(), assemble(&inst)) {
+                    (Ok(addr), Ok(mut insts))
+                        if addr < self.vm.instructions.len() && insts.len() == 1 =>
+                    {
+                        let new = insts.remove(0);
+                        let old = &mut self.vm.instructions[addr];
+                        old.op = new.op;
+                        old.arg = new.arg;
+                        Ok(format!("patched {}: {}\n", addr, old))
+                    }
+                    (_, Err(e)) => Err(format!("bad patch: {}\n", e)),
+                    _ => Err(format!("bad patch: {} {}\n", addr, inst)),
+                }
+            }
+            ["output"] | ["o"] => {
+                let vals: Vec<String> = self.vm.output.iter().map(|v| v.to_string()).collect();
+                Ok(format!("output: {}\n", vals.join(", ")))
+            }
+            ["list"] | ["l"] => {
+                let mut out = String::new();
+                for (n, b) in self.breakpoints.iter().enumerate() {
+                    out.push_str(&format!("breakpoint {}: {}\n", n, b));
+                }
+                for w in &self.watches {
+                    out.push_str(&format!("watch {}\n", w));
+                }
+                for (addr, inst) in self.vm.instructions.iter().enumerate() {
+                    let marker = if addr as i64 == self.vm.ip { ">" } else { " " };
+                    out.push_str(&format!(
+                        "{} {:4}: {} (ran {})\n",
+                        marker, addr, inst, inst.runcount
+                    ));
+                }
+                Ok(out)
+            }
+            _ => Err(format!("unknown command: {}\n", command.trim())),
+        };
+        Some(out.unwrap_or_else(|e| e))
+    }
+}
+
+/// Line oriented debugger session: reads commands from `input` until `quit`
+/// or end of input, writing a prompt and each command's output to `output`.
+fn run_debugger<R: BufRead, W: Write>(debugger: &mut Debugger, input: R, mut output: W) {
+    write!(output, "{}", debugger.status()).unwrap();
+    write!(output, "(dbg) ").unwrap();
+    output.flush().unwrap();
+    for line in input.lines() {
+        match debugger.execute(&line.unwrap()) {
+            Some(out) => write!(output, "{}(dbg) ", out).unwrap(),
+            None => break,
+        }
+        output.flush().unwrap();
+    }
+}
+
+pub fn day08_debug(lines: &[String]) {
+    let instructions = match assemble(&lines.join("\n")) {
+        Ok(instructions) => instructions,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let registers = instructions
+        .iter()
+        .flat_map(|inst| inst.op.registers())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let config = VmConfig {
+        registers,
+        ..VmConfig::default()
+    };
+    let mut debugger = Debugger::new(instructions, config);
+    let stdin = std::io::stdin();
+    run_debugger(&mut debugger, stdin.lock(), std::io::stdout());
+}
+
+/// Static control flow graph of a program. Node `len` is the exit: reaching
+/// it means the program terminated. Jumps outside `0..=len` have no
+/// successor. Conditional jumps depend on register values, so they get a
+/// `branch` edge next to their fall through `succ` and `terminates` only
+/// says whether the exit can be reached.
+#[derive(Debug)]
+struct Cfg {
+    len: usize,
+    succ: Vec<Option<usize>>,
+    branch: Vec<Option<usize>>,
+    flipped: Vec<Option<usize>>,
+    terminates: Vec<bool>,
+}
+
+impl Cfg {
+    fn new(instructions: &[Inst]) -> Cfg {
+        let len = instructions.len();
+        let target = |addr: usize, offset: i64| -> Option<usize> {
+            let t = addr as i64 + offset;
+            if t >= 0 && t <= len as i64 {
+                Some(t as usize)
+            } else {
+                None
+            }
+        };
+        let mut succ = Vec::with_capacity(len);
+        let mut branch = Vec::with_capacity(len);
+        let mut flipped = Vec::with_capacity(len);
+        for (addr, inst) in instructions.iter().enumerate() {
+            let (s, b, f) = match inst.op {
+                Op::JMP => (target(addr, inst.arg), None, target(addr, 1)),
+                Op::NOP => (target(addr, 1), None, target(addr, inst.arg)),
+                Op::Jz(_) | Op::Jnz(_) => (target(addr, 1), target(addr, inst.arg), None),
+                _ => (target(addr, 1), None, None),
+            };
+            succ.push(s);
+            branch.push(b);
+            flipped.push(f);
+        }
+
+        // Walk predecessors back from the exit to mark every node that
+        // terminates when left unpatched.
+        let mut preds = vec![Vec::new(); len + 1];
+        for (addr, (s, b)) in succ.iter().zip(&branch).enumerate() {
+            for next in s.iter().chain(b.iter()) {
+                preds[*next].push(addr);
+            }
+        }
+        let mut terminates = vec![false; len + 1];
+        terminates[len] = true;
+        let mut queue = vec![len];
+        while let Some(node) = queue.pop() {
+            for p in &preds[node] {
+                if !terminates[*p] {
+                    terminates[*p] = true;
+                    queue.push(*p);
+                }
+            }
+        }
+
+        Cfg {
+            len,
+            succ,
+            branch,
+            flipped,
+            terminates,
+        }
+    }
+
+    /// Addresses executed from the start, in order, until the program exits,
+    /// jumps out of bounds, comes back to an address it already ran, or hits
+    /// a conditional jump that can't be followed statically.
+    fn trace(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.len];
+        let mut path = Vec::new();
+        let mut node = 0;
+        while node < self.len && !seen[node] {
+            seen[node] = true;
+            path.push(node);
+            if self.branch[node].is_some() {
+                break;
+            }
+            match self.succ[node] {
+                Some(s) => node = s,
+                None => break,
+            }
+        }
+        path
+    }
+
+    /// The instructions forming the infinite loop, starting with the first
+    /// one to be repeated, or `None` if the program does not loop (or a
+    /// conditional jump makes it impossible to tell).
+    #[allow(dead_code)]
+    fn find_loop(&self) -> Option<Vec<usize>> {
+        let path = self.trace();
+        let last = *path.last()?;
+        if self.branch[last].is_some() {
+            return None;
+        }
+        let next = self.succ[last]?;
+        let start = path.iter().position(|n| *n == next)?;
+        Some(path[start..].to_vec())
+    }
+
+    /// The single JMP/NOP swap that makes the program terminate. Only
+    /// instructions on the path from the start matter, and every one of
+    /// those fails to terminate unpatched, so the swapped edge can't lead
+    /// back through the swap: one lookup per instruction is enough. Only
+    /// exact for programs without conditional jumps.
+    fn repair_one_flip(&self) -> Option<usize> {
+        if self.terminates[0] {
+            return None;
+        }
+        self.trace()
+            .into_iter()
+            .find(|n| matches!(self.flipped[*n], Some(f) if self.terminates[f]))
+    }
+
+    /// The smallest set of JMP/NOP swaps that makes the program terminate,
+    /// found with a 0-1 BFS where following an instruction costs nothing
+    /// and swapping it costs one. Shortest paths never revisit a node, so
+    /// each instruction is taken either swapped or not, never both.
+    /// Conditional jumps are assumed to go whichever way is cheaper.
+    #[allow(dead_code)]
+    fn repair_k_flips(&self) -> Option<Vec<usize>> {
+        let mut dist = vec![usize::MAX; self.len + 1];
+        let mut via: Vec<Option<(usize, bool)>> = vec![None; self.len + 1];
+        let mut queue = VecDeque::new();
+        dist[0] = 0;
+        queue.push_back(0);
+        while let Some(node) = queue.pop_front() {
+            if node == self.len {
+                break;
+            }
+            let edges = [
+                (self.succ[node], 0, false),
+                (self.branch[node], 0, false),
+                (self.flipped[node], 1, true),
+            ];
+            for (next, cost, flip) in edges.iter() {
+                if let Some(next) = next {
+                    if dist[node] + cost < dist[*next] {
+                        dist[*next] = dist[node] + cost;
+                        via[*next] = Some((node, *flip));
+                        if *cost == 0 {
+                            queue.push_front(*next);
+                        } else {
+                            queue.push_back(*next);
+                        }
+                    }
+                }
+            }
+        }
+        if dist[self.len] == usize::MAX {
+            return None;
+        }
+
+        let mut flips = Vec::new();
+        let mut node = self.len;
+        while let Some((prev, flip)) = via[node] {
+            if flip {
+                flips.push(prev);
+            }
+            node = prev;
+        }
+        flips.sort_unstable();
+        Some(flips)
+    }
+}
+
+/// Runs a program to completion, returning the accumulator if it reaches
+/// the instruction just past the end without repeating itself.
+fn run_to_exit(instructions: Vec<Inst>) -> Option<i64> {
+    let mut vm = VM::new(instructions, VmConfig::default());
+    match vm.run() {
+        Halt::Terminated => Some(vm.accumulator()),
+        _ => None,
+    }
+}
+
+fn day08a(lines: &[String]) -> i64 {
+    let instructions: Vec<Inst> = lines.iter().map(|line| parse_line(line).unwrap()).collect();
+    let mut vm = VM::new(instructions, VmConfig::default());
+    vm.run();
+    vm.accumulator()
 }
 
 fn fix_inst(is: &[Inst], linenum: usize) -> Vec<Inst> {
@@ -79,25 +1051,10 @@ fn fix_inst(is: &[Inst], linenum: usize) -> Vec<Inst> {
 
 fn day08b(lines: &[String]) -> i64 {
     let instructions: Vec<Inst> = lines.iter().map(|line| parse_line(line).unwrap()).collect();
-    let instlen = lines.len();
-    for fixline in 0..instlen {
-        let fixed = fix_inst(&instructions, fixline);
-        let mut vm = VM {
-            instructions: fixed,
-            ip: 0,
-            accumulator: 0,
-        };
-        while (vm.ip as usize) < instlen {
-            if vm.instructions.get(vm.ip as usize).unwrap().runcount > 0 {
-                break;
-            }
-            vm.step();
-            if (vm.ip as usize) >= instlen {
-                return vm.accumulator;
-            }
-        }
+    match Cfg::new(&instructions).repair_one_flip() {
+        Some(fixline) => run_to_exit(fix_inst(&instructions, fixline)).unwrap(),
+        None => 0,
     }
-    0
 }
 
 pub fn day08(lines: &[String], part: char) -> i64 {
@@ -111,6 +1068,8 @@ pub fn day08(lines: &[String], part: char) -> i64 {
 #[cfg(test)]
 mod tests {
     use crate::day08;
+    use proptest::prelude::*;
+
     #[test]
     fn test_case() {
         let test_inst = "acc +1";
@@ -148,4 +1107,282 @@ mod tests {
         let day08b = day08::day08b(&lines);
         assert_eq!(day08b, 8);
     }
+
+    #[test]
+    fn test_assembler() {
+        let source = "; the example program, with labels
+        nop +0
+    top:
+        acc +1
+        jmp skip   ; over the loop
+    back: acc +3
+        jmp top
+        acc -99
+    skip:
+        acc +1
+        jmp back
+        acc +6";
+        let original: Vec<day08::Inst> = "nop +0
+        acc +1
+        jmp +4
+        acc +3
+        jmp -3
+        acc -99
+        acc +1
+        jmp -4
+        acc +6"
+            .split('\n')
+            .map(|line| day08::parse_line(line).unwrap())
+            .collect();
+        assert_eq!(day08::assemble(source).unwrap(), original);
+
+        let canonical = "L0:
+    nop L0
+L1:
+    acc +1
+    jmp L6
+L3:
+    acc +3
+    jmp L1
+    acc -99
+L6:
+    acc +1
+    jmp L3
+    acc +6
+";
+        assert_eq!(day08::disassemble(&original), canonical);
+        assert_eq!(day08::assemble(canonical).unwrap(), original);
+
+        let err = day08::assemble("acc +1\n  frob 3").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.span, 2..6);
+        assert_eq!(
+            err.to_string(),
+            "line 2, columns 3-6: unknown opcode `frob`"
+        );
+        let err = day08::assemble("a: nop +0\na: acc +1").unwrap_err();
+        assert_eq!(err.message, "duplicate label `a`");
+        let err = day08::assemble("acc label").unwrap_err();
+        assert_eq!(err.span, 4..9);
+        let err = day08::assemble("jmp +1 +2").unwrap_err();
+        assert_eq!(err.message, "unexpected `+2`");
+        let err = day08::assemble("jmp nowhere").unwrap_err();
+        assert_eq!(err.message, "undefined label `nowhere`");
+        let err = day08::assemble("jmp").unwrap_err();
+        assert_eq!(err.message, "`jmp` needs an operand");
+    }
+
+    #[test]
+    fn test_assembler_roundtrip() {
+        let op = prop_oneof![
+            Just(day08::Op::ACC),
+            Just(day08::Op::JMP),
+            Just(day08::Op::NOP),
+            (0usize..4).prop_map(day08::Op::Mul),
+            (0usize..4).prop_map(day08::Op::Set),
+            (0usize..4, 0usize..4).prop_map(|(a, b)| day08::Op::Cpy(a, b)),
+            (0usize..4).prop_map(day08::Op::Jz),
+            (0usize..4).prop_map(day08::Op::Jnz),
+            (0usize..4).prop_map(day08::Op::Out),
+        ];
+        let program = prop::collection::vec((op, -40i64..40), 0..40);
+        proptest!(|(program in program)| {
+            let insts: Vec<day08::Inst> = program
+                .into_iter()
+                .map(|(op, arg)| {
+                    let arg = if op.takes_arg() { arg } else { 0 };
+                    day08::Inst { op, arg, runcount: 0 }
+                })
+                .collect();
+            let text = day08::disassemble(&insts);
+            let reassembled = day08::assemble(&text).unwrap();
+            prop_assert_eq!(&reassembled, &insts);
+            prop_assert_eq!(day08::disassemble(&reassembled), text);
+        });
+    }
+
+    #[test]
+    fn test_debugger() {
+        let program = day08::assemble(
+            "nop +0
+            acc +1
+            jmp +4
+            acc +3
+            jmp -3
+            acc -99
+            acc +1
+            jmp -4
+            acc +6",
+        )
+        .unwrap();
+        let mut dbg = day08::Debugger::new(program, day08::VmConfig::default());
+
+        assert_eq!(dbg.step(), day08::Stop::Stepped);
+        assert_eq!(dbg.cont(), day08::Stop::Halt(day08::Halt::LoopDetected));
+        assert_eq!(dbg.vm.ip, 1);
+        assert_eq!(dbg.vm.accumulator(), 5);
+        assert_eq!(dbg.history.len(), 7);
+
+        assert_eq!(dbg.rewind(3), 3);
+        assert_eq!(dbg.vm.ip, 7);
+        assert_eq!(dbg.vm.accumulator(), 2);
+        assert_eq!(dbg.vm.instructions[7].runcount, 0);
+        assert_eq!(dbg.rewind(100), 4);
+        assert_eq!(dbg.vm.ip, 0);
+        assert_eq!(dbg.vm.accumulator(), 0);
+
+        dbg.breakpoints.push(day08::Breakpoint::Address(3));
+        dbg.breakpoints.push(day08::Breakpoint::Condition(
+            day08::Expr::Acc,
+            day08::Cmp::Ge,
+            2,
+        ));
+        assert_eq!(dbg.cont(), day08::Stop::Breakpoint(1));
+        assert_eq!(dbg.vm.ip, 7);
+        assert_eq!(dbg.cont(), day08::Stop::Breakpoint(0));
+        assert_eq!(dbg.vm.ip, 3);
+
+        let input = "watch acc
+        print runs[1]
+        back 2
+        patch 7 nop -4
+        delete 0
+        delete 0
+        continue
+        frobnicate
+        quit
+        step";
+        let mut out: Vec<u8> = Vec::new();
+        day08::run_debugger(&mut dbg, input.as_bytes(), &mut out);
+        let out = String::from_utf8(out).unwrap();
+        let expected = "ip = 3, acc = 2, next: acc +3
+(dbg) watching acc = 2
+(dbg) runs[1] = 1
+(dbg) rewound 2 steps
+ip = 6, acc = 1, next: acc +1
+  acc = 1
+(dbg) patched 7: nop -4
+(dbg) deleted breakpoint 0 (address 3)
+(dbg) deleted breakpoint 0 (acc >= 2)
+(dbg) program terminated
+ip = 9, acc = 8
+  acc = 8
+(dbg) unknown command: frobnicate
+(dbg) ";
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_cfg() {
+        let program = day08::assemble(
+            "nop +0
+            acc +1
+            jmp +4
+            acc +3
+            jmp -3
+            acc -99
+            acc +1
+            jmp -4
+            acc +6",
+        )
+        .unwrap();
+        let cfg = day08::Cfg::new(&program);
+        assert_eq!(cfg.find_loop(), Some(vec![1, 2, 6, 7, 3, 4]));
+        assert_eq!(
+            cfg.terminates,
+            vec![false, false, false, false, false, false, false, false, true, true]
+        );
+        assert_eq!(cfg.repair_one_flip(), Some(7));
+        assert_eq!(cfg.repair_k_flips(), Some(vec![7]));
+        assert_eq!(day08::run_to_exit(day08::fix_inst(&program, 7)), Some(8));
+        assert_eq!(day08::run_to_exit(program), None);
+
+        // Needs both jumps swapped; no single swap will do.
+        let program = day08::assemble("jmp +0\njmp +0\nacc +1").unwrap();
+        let cfg = day08::Cfg::new(&program);
+        assert_eq!(cfg.repair_one_flip(), None);
+        assert_eq!(cfg.repair_k_flips(), Some(vec![0, 1]));
+
+        let program = day08::assemble("acc +1\njmp +1").unwrap();
+        let cfg = day08::Cfg::new(&program);
+        assert_eq!(cfg.find_loop(), None);
+        assert_eq!(cfg.repair_one_flip(), None);
+        assert_eq!(cfg.repair_k_flips(), Some(vec![]));
+
+        let program = day08::assemble("acc +1\njmp -1").unwrap();
+        assert_eq!(day08::Cfg::new(&program).repair_k_flips(), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_extended_vm() {
+        use day08::{Halt, LoopCheck, VmConfig, VM};
+
+        let source = "    set r1 3
+        loop:
+            out r1
+            cpy r1 r0
+            acc -1
+            cpy r0 r1
+            jnz r1 loop
+            set r2 7
+            mul r2 6
+            out r2";
+        let program = day08::assemble(source).unwrap();
+        assert_eq!(program[5].op, day08::Op::Jnz(1));
+        assert_eq!(program[5].arg, -4);
+
+        let config = VmConfig {
+            registers: 3,
+            loop_check: LoopCheck::State,
+            ..VmConfig::default()
+        };
+        let mut vm = VM::new(program.clone(), config.clone());
+        assert_eq!(vm.run(), Halt::Terminated);
+        assert_eq!(vm.output, vec![3, 2, 1, 42]);
+        assert_eq!(vm.registers, vec![0, 0, 42]);
+        assert_eq!(vm.steps, 19);
+
+        let mut vm = VM::new(program.clone(), VmConfig::default());
+        assert_eq!(vm.run(), Halt::InvalidRegister(1));
+
+        let mut vm = VM::new(
+            program.clone(),
+            VmConfig {
+                loop_check: LoopCheck::Revisit,
+                ..config.clone()
+            },
+        );
+        assert_eq!(vm.run(), Halt::LoopDetected);
+        assert_eq!(vm.output, vec![3]);
+
+        let mut vm = VM::new(
+            program,
+            VmConfig {
+                max_steps: Some(5),
+                ..config.clone()
+            },
+        );
+        assert_eq!(vm.run(), Halt::StepLimit);
+        assert_eq!(vm.steps, 5);
+
+        let spin = day08::assemble("set r1 1\nspin: jnz r1 spin").unwrap();
+        let mut vm = VM::new(spin, config.clone());
+        assert_eq!(vm.run(), Halt::LoopDetected);
+
+        let escape = day08::assemble("acc +1\njmp -5").unwrap();
+        let mut vm = VM::new(escape, config);
+        assert_eq!(vm.run(), Halt::InvalidIp);
+        assert_eq!(vm.ip, -4);
+        assert_eq!(vm.accumulator(), 1);
+
+        let err = day08::assemble("cpy r0 acc").unwrap_err();
+        assert_eq!(err.message, "invalid register `acc`");
+        assert_eq!(err.span, 7..10);
+        let err = day08::assemble("out").unwrap_err();
+        assert_eq!(err.message, "`out` needs a register");
+        let err = day08::assemble("out r1 +2").unwrap_err();
+        assert_eq!(err.message, "unexpected `+2`");
+        let err = day08::assemble("mul r1 top").unwrap_err();
+        assert_eq!(err.message, "invalid operand `top` for `mul r1`");
+    }
 }
@@ -1,5 +1,8 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
 
+/// The brute-force check `XmasValidator` replaced, kept to test it against.
+#[cfg(test)]
 fn is_valid(num: i64, v: &VecDeque<i64>) -> bool {
     for i in 0..v.len() {
         for j in i + 1..v.len() {
@@ -11,6 +14,7 @@ fn is_valid(num: i64, v: &VecDeque<i64>) -> bool {
     false
 }
 
+#[cfg(test)]
 fn prep(nums: &[i64], preamblelen: usize) -> VecDeque<i64> {
     let mut retval = VecDeque::new();
     for num in nums.iter().take(preamblelen) {
@@ -19,29 +23,107 @@ fn prep(nums: &[i64], preamblelen: usize) -> VecDeque<i64> {
     retval
 }
 
-fn find_contiguous_sum(nums: &[i64], target: i64) -> Vec<i64> {
-    for i in 0..nums.len() {
-        for j in i + 1..nums.len() {
-            let sum: i64 = nums[i..j].iter().sum();
-            if sum == target {
-                return nums[i..j].to_vec();
+/// Checks XMAS numbers one at a time against the `preamble` numbers before
+/// them. Alongside the window it keeps a multiset of the sums of every pair
+/// of distinct values in it, so a check is a single lookup and sliding the
+/// window costs O(w).
+#[derive(Debug)]
+struct XmasValidator {
+    preamble: usize,
+    window: VecDeque<i64>,
+    sums: HashMap<i64, usize>,
+}
+
+impl XmasValidator {
+    fn new(preamble: usize) -> XmasValidator {
+        XmasValidator {
+            preamble,
+            window: VecDeque::with_capacity(preamble + 1),
+            sums: HashMap::new(),
+        }
+    }
+
+    /// Feeds the next number. Returns `None` while the preamble is still
+    /// filling up, otherwise whether `num` is the sum of two distinct
+    /// values in the window.
+    fn push(&mut self, num: i64) -> Option<bool> {
+        let valid = if self.window.len() < self.preamble {
+            None
+        } else {
+            Some(self.sums.contains_key(&num))
+        };
+
+        for x in &self.window {
+            if *x != num {
+                *self.sums.entry(x + num).or_insert(0) += 1;
+            }
+        }
+        self.window.push_back(num);
+
+        if self.window.len() > self.preamble {
+            let old = self.window.pop_front().unwrap();
+            for x in &self.window {
+                if *x != old {
+                    let count = self.sums.get_mut(&(x + old)).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        self.sums.remove(&(x + old));
+                    }
+                }
             }
         }
+        valid
     }
-    vec![]
 }
 
-fn day09a(nums: &[i64], preamblelen: usize) -> i64 {
-    let mut v = prep(nums, preamblelen);
-    for num in nums.iter().skip(preamblelen) {
-        if !is_valid(*num, &v) {
-            return *num;
-        } else {
-            v.pop_front();
-            v.push_back(*num);
+fn first_invalid(nums: &[i64], preamblelen: usize) -> Option<i64> {
+    let mut validator = XmasValidator::new(preamblelen);
+    nums.iter()
+        .copied()
+        .find(|num| validator.push(*num) == Some(false))
+}
+
+/// Every range of at least two consecutive numbers summing to `target`.
+/// Uses two pointers, so `nums` must not contain negative numbers: for a
+/// fixed start the sum only grows as the end moves right, and the smallest
+/// matching end never moves left as the start does. Runs of zeros after a
+/// match give further matches with the same start.
+fn contiguous_ranges(nums: &[i64], target: i64) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut end = 0;
+    let mut sum = 0;
+    for start in 0..nums.len() {
+        while end < nums.len() && (end <= start || sum < target) {
+            sum += nums[end];
+            end += 1;
         }
+        if sum == target {
+            let mut e = end;
+            loop {
+                if e - start >= 2 {
+                    ranges.push(start..e);
+                }
+                if e < nums.len() && nums[e] == 0 {
+                    e += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+        sum -= nums[start];
     }
-    0
+    ranges
+}
+
+fn find_contiguous_sum(nums: &[i64], target: i64) -> Vec<i64> {
+    match contiguous_ranges(nums, target).into_iter().next() {
+        Some(range) => nums[range].to_vec(),
+        None => vec![],
+    }
+}
+
+fn day09a(nums: &[i64], preamblelen: usize) -> i64 {
+    first_invalid(nums, preamblelen).unwrap_or(0)
 }
 
 fn day09b(nums: &[i64], preamblelen: usize) -> i64 {
@@ -51,10 +133,10 @@ fn day09b(nums: &[i64], preamblelen: usize) -> i64 {
     v[0] + v[v.len() - 1]
 }
 
-pub fn day09(nums: &[i64], part: char) -> i64 {
+pub fn day09(nums: &[i64], part: char, preamblelen: usize) -> i64 {
     match part {
-        'a' => day09a(nums, 25),
-        'b' => day09b(nums, 25),
+        'a' => day09a(nums, preamblelen),
+        'b' => day09b(nums, preamblelen),
         _ => 0,
     }
 }
@@ -62,6 +144,7 @@ pub fn day09(nums: &[i64], part: char) -> i64 {
 #[cfg(test)]
 mod tests {
     use day09::find_contiguous_sum;
+    use std::collections::VecDeque;
 
     use crate::day09;
 
@@ -97,5 +180,46 @@ mod tests {
         assert_eq!(day09::day09a(&nums, 5), 127);
 
         assert_eq!(find_contiguous_sum(&nums, 127), vec![15, 25, 47, 40]);
+        assert_eq!(day09::day09b(&nums, 5), 62);
+    }
+
+    #[test]
+    fn test_validator() {
+        let nums: Vec<i64> = (1..=25).collect();
+        let mut validator = day09::XmasValidator::new(25);
+        for num in &nums {
+            assert_eq!(validator.push(*num), None);
+        }
+        assert_eq!(validator.push(26), Some(true));
+        assert_eq!(validator.push(49), Some(true));
+        assert_eq!(validator.push(100), Some(false));
+        // 1 and 2 have left the window, and 100 + 100 uses the same value twice.
+        assert_eq!(validator.push(3), Some(false));
+        assert_eq!(validator.push(200), Some(false));
+
+        let mut nums: Vec<i64> = vec![20, 1, 19, 5, 15, 7, 13, 40, 2, 33];
+        let mut window: VecDeque<i64> = VecDeque::new();
+        let mut validator = day09::XmasValidator::new(4);
+        nums.extend(nums.clone().iter().map(|x| x * 3));
+        for num in nums {
+            let expected = if window.len() < 4 {
+                None
+            } else {
+                Some(day09::is_valid(num, &window))
+            };
+            assert_eq!(validator.push(num), expected);
+            window.push_back(num);
+            if window.len() > 4 {
+                window.pop_front();
+            }
+        }
+
+        assert_eq!(
+            day09::contiguous_ranges(&[1, 2, 0, 3, 0, 0, 3, 3], 6),
+            vec![0..4, 0..5, 0..6, 2..7, 3..7, 4..8, 5..8, 6..8]
+        );
+        assert_eq!(day09::contiguous_ranges(&[0, 0, 5], 0), vec![0..2]);
+        assert_eq!(day09::contiguous_ranges(&[6, 1], 6), vec![]);
+        assert_eq!(day09::first_invalid(&[1, 2, 3], 2), None);
     }
 }
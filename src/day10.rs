@@ -1,75 +1,197 @@
 use std::collections::HashMap;
+use std::hash::Hash;
+
+use num::{BigUint, One, Zero};
 
 #[derive(Debug)]
 struct Graph {
     nodes: HashMap<i64, Node>,
+    device: i64,
 }
 #[derive(Debug)]
 struct Node {
     id: usize,
-    pathcount: usize,
+    pathcount: Option<BigUint>,
     children: Vec<i64>,
 }
 
-fn count_paths(graph: &mut Graph, nodenum: i64) -> usize {
-    let node = graph.nodes.get_mut(&nodenum).unwrap();
-    if node.pathcount != 0 {
-        node.pathcount
-    } else if node.children.is_empty() {
-        node.pathcount = 1;
-        1
-    } else {
-        let mut sum = 0;
-        let kids = node.children.to_vec();
-        for c in kids {
-            {
-                let paths = count_paths(graph, c);
-                sum += paths;
+impl Graph {
+    /// Graphviz DOT rendering of the adapter chain, each node labelled with
+    /// its joltage and the number of arrangements from it to the device
+    /// (`?` where that hasn't been counted yet).
+    fn to_dot(&self) -> String {
+        let mut keys: Vec<&i64> = self.nodes.keys().collect();
+        keys.sort();
+        let mut dot = String::from("digraph adapters {\n");
+        for k in &keys {
+            let node = &self.nodes[k];
+            let paths = match &node.pathcount {
+                Some(count) => count.to_string(),
+                None => "?".to_string(),
+            };
+            dot.push_str(&format!(
+                "    {} [label=\"{}\\npaths={}\"];\n",
+                k, node.id, paths
+            ));
+        }
+        for k in &keys {
+            for c in &self.nodes[k].children {
+                dot.push_str(&format!("    {} -> {};\n", k, c));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Lazily enumerates every arrangement of adapters from the outlet to
+    /// the device, each as the full chain of joltages. Path counts are
+    /// filled in first so dead ends are never explored.
+    #[allow(dead_code)]
+    fn arrangements(&mut self) -> Arrangements<'_> {
+        count_paths(self, 0);
+        Arrangements {
+            graph: self,
+            stack: vec![(0, 0)],
+        }
+    }
+}
+
+/// Number of paths from `start` to `target` in a DAG, where `children`
+/// lists a node's successors. Counts for every node visited are kept in
+/// `memo`, so later queries only do new work. The walk uses an explicit
+/// stack, so long chains can't overflow the call stack.
+fn count_dag_paths<N, F>(
+    start: N,
+    target: N,
+    children: F,
+    memo: &mut HashMap<N, BigUint>,
+) -> BigUint
+where
+    N: Copy + Eq + Hash,
+    F: Fn(N) -> Vec<N>,
+{
+    let mut stack = vec![(start, false)];
+    while let Some((node, expanded)) = stack.pop() {
+        if memo.contains_key(&node) {
+            continue;
+        }
+        if node == target {
+            memo.insert(node, BigUint::one());
+        } else if expanded {
+            let count = children(node)
+                .iter()
+                .fold(BigUint::zero(), |acc, c| acc + &memo[c]);
+            memo.insert(node, count);
+        } else {
+            stack.push((node, true));
+            for c in children(node) {
+                if !memo.contains_key(&c) {
+                    stack.push((c, false));
+                }
+            }
+        }
+    }
+    memo[&start].clone()
+}
+
+fn count_paths(graph: &mut Graph, nodenum: i64) -> BigUint {
+    let mut memo: HashMap<i64, BigUint> = graph
+        .nodes
+        .iter()
+        .filter_map(|(k, node)| node.pathcount.clone().map(|count| (*k, count)))
+        .collect();
+    let nodes = &graph.nodes;
+    let count = count_dag_paths(
+        nodenum,
+        graph.device,
+        |n| nodes[&n].children.clone(),
+        &mut memo,
+    );
+    for (k, c) in memo {
+        graph.nodes.get_mut(&k).unwrap().pathcount = Some(c);
+    }
+    count
+}
+
+/// Depth-first iterator over adapter arrangements. Each stack entry is a
+/// joltage and the index of the next child of it to try.
+#[allow(dead_code)]
+#[derive(Debug)]
+struct Arrangements<'a> {
+    graph: &'a Graph,
+    stack: Vec<(i64, usize)>,
+}
+
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Vec<i64>> {
+        loop {
+            let (node, next_child) = *self.stack.last()?;
+            if node == self.graph.device {
+                let chain = self.stack.iter().map(|(n, _)| *n).collect();
+                self.stack.pop();
+                return Some(chain);
+            }
+            let children = &self.graph.nodes[&node].children;
+            match children.get(next_child) {
+                Some(c) => {
+                    self.stack.last_mut().unwrap().1 += 1;
+                    let viable = match &self.graph.nodes[c].pathcount {
+                        Some(count) => !count.is_zero(),
+                        None => true,
+                    };
+                    if viable {
+                        self.stack.push((*c, 0));
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                }
             }
         }
-        graph.nodes.get_mut(&nodenum).unwrap().pathcount = sum;
-        sum
     }
 }
 
 fn make_graph(nums: &[i64]) -> Graph {
+    make_graph_with_gap(nums, 3)
+}
+
+/// Adapter graph where each adapter can feed any adapter rated 1 to
+/// `max_gap` jolts higher. The device is rated `max_gap` above the highest
+/// adapter.
+fn make_graph_with_gap(nums: &[i64], max_gap: i64) -> Graph {
     let mut sortednums = nums.to_vec();
     sortednums.push(0);
     sortednums.sort_unstable();
-    let maxplus3 = sortednums[sortednums.len() - 1] + 3;
-    sortednums.push(maxplus3);
+    let device = sortednums[sortednums.len() - 1] + max_gap;
+    sortednums.push(device);
     sortednums.sort_unstable();
 
     let last_node = Node {
-        id: maxplus3 as usize,
-        pathcount: 0,
+        id: device as usize,
+        pathcount: None,
         children: vec![],
     };
     let mut nodes: HashMap<i64, Node> = HashMap::new();
-    nodes.insert(maxplus3, last_node);
+    nodes.insert(device, last_node);
     for i in (0..sortednums[sortednums.len() - 1]).rev() {
-        if sortednums.contains(&i) {
+        if sortednums.binary_search(&i).is_ok() {
             let mut children = Vec::new();
-            for j in i + 1..i + 4 {
+            for j in i + 1..=i + max_gap {
                 if nodes.contains_key(&j) {
                     children.push(j);
                 }
             }
             let node = Node {
                 id: i as usize,
-                pathcount: 0,
+                pathcount: None,
                 children,
             };
             nodes.insert(i, node);
         }
     }
-    Graph { nodes }
-}
-
-fn day10b(nums: &[i64]) -> i64 {
-    let mut graph = make_graph(nums);
-    let paths = count_paths(&mut graph, 0);
-    paths as i64
+    Graph { nodes, device }
 }
 
 fn day10a(nums: &[i64]) -> i64 {
@@ -87,10 +209,23 @@ fn day10a(nums: &[i64]) -> i64 {
     (num_ones * num_threes) as i64
 }
 
+pub fn day10_dot(nums: &[i64]) -> String {
+    let mut graph = make_graph(nums);
+    count_paths(&mut graph, 0);
+    graph.to_dot()
+}
+
+/// Number of ways to arrange the adapters, which can outgrow any fixed-size
+/// integer on long chains.
+pub fn day10_count(nums: &[i64]) -> BigUint {
+    let mut graph = make_graph(nums);
+    count_paths(&mut graph, 0)
+}
+
+/// Part B's count can overflow an `i64`, so it comes from `day10_count`.
 pub fn day10(nums: &[i64], part: char) -> i64 {
     match part {
         'a' => day10a(nums),
-        'b' => day10b(nums),
         _ => 0,
     }
 }
@@ -98,6 +233,7 @@ pub fn day10(nums: &[i64], part: char) -> i64 {
 #[cfg(test)]
 mod tests {
     use day10::{count_paths, make_graph};
+    use num::BigUint;
 
     use crate::day10;
     #[test]
@@ -160,7 +296,71 @@ mod tests {
 
         let mut g1 = make_graph(&nums);
         let mut g2 = make_graph(&nums2);
-        assert_eq!(count_paths(&mut g1, 0), 8);
-        assert_eq!(count_paths(&mut g2, 0), 19208);
+        assert_eq!(count_paths(&mut g1, 0), BigUint::from(8u32));
+        assert_eq!(count_paths(&mut g2, 0), BigUint::from(19208u32));
+        assert_eq!(day10::day10_count(&nums2), BigUint::from(19208u32));
+
+        let dot = day10::day10_dot(&[1, 2]);
+        assert_eq!(
+            dot,
+            "digraph adapters {
+    0 [label=\"0\\npaths=2\"];
+    1 [label=\"1\\npaths=1\"];
+    2 [label=\"2\\npaths=1\"];
+    5 [label=\"5\\npaths=1\"];
+    0 -> 1;
+    0 -> 2;
+    1 -> 2;
+    2 -> 5;
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let mut graph = day10::make_graph(&[1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19]);
+        let arrangements: Vec<Vec<i64>> = graph.arrangements().collect();
+        assert_eq!(arrangements.len(), 8);
+        assert_eq!(
+            arrangements[0],
+            vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22]
+        );
+        assert_eq!(arrangements[7], vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+        for chain in &arrangements {
+            assert!(chain
+                .windows(2)
+                .all(|w| w[1] - w[0] >= 1 && w[1] - w[0] <= 3));
+        }
+
+        let mut graph = day10::make_graph_with_gap(&[1, 2, 4, 5, 6], 2);
+        assert_eq!(count_paths(&mut graph, 0), BigUint::from(4u32));
+        assert_eq!(graph.arrangements().count(), 4);
+        // 1 is a dead end once the gap is only 1.
+        let mut graph = day10::make_graph_with_gap(&[1, 3, 4, 5], 1);
+        assert_eq!(count_paths(&mut graph, 0), BigUint::from(0u32));
+        assert_eq!(graph.arrangements().next(), None);
+
+        // Every joltage from 1 to 200 gives a tribonacci number of paths,
+        // far past what fits in 64 bits.
+        let nums: Vec<i64> = (1..=200).collect();
+        let mut graph = day10::make_graph(&nums);
+        let (mut a, mut b, mut c) = (
+            BigUint::from(0u32),
+            BigUint::from(0u32),
+            BigUint::from(1u32),
+        );
+        for _ in 0..200 {
+            let next = &a + &b + &c;
+            a = b;
+            b = c;
+            c = next;
+        }
+        assert_eq!(count_paths(&mut graph, 0), c);
+        assert!(count_paths(&mut graph, 0) > BigUint::from(u64::MAX));
+        assert_eq!(day10::day10_count(&nums), c);
+        let mut third: Vec<i64> = (0..=200).filter(|x| *x != 198).collect();
+        third.push(203);
+        assert_eq!(graph.arrangements().nth(2), Some(third));
     }
 }
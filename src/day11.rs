@@ -1,13 +1,132 @@
 use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+use crate::frames::{Frame, FrameRecorder};
+use crate::history::{Cycle, History};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum CellState {
     Floor,
     Free,
     Occupied,
 }
 
+fn parse_state(c: char) -> Option<CellState> {
+    match c {
+        '.' => Some(CellState::Floor),
+        'L' => Some(CellState::Free),
+        '#' => Some(CellState::Occupied),
+        _ => None,
+    }
+}
+
+const DIRECTIONS: [(i64, i64); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+/// Which cells count as a seat's neighbours.
 #[derive(Debug, Clone, PartialEq)]
+enum Neighborhood {
+    /// The eight surrounding cells.
+    Adjacent,
+    /// The first non-transparent cell in each of the eight directions, no
+    /// further away than the given distance (unlimited if `None`).
+    LineOfSight(Option<usize>),
+    /// The cells at exactly these `(dx, dy)` offsets.
+    Custom(Vec<(i64, i64)>),
+}
+
+/// The rules of the seating automaton. A free seat fills up when at most
+/// `occupy_at_most` of its neighbours are occupied, and an occupied seat
+/// empties when at least `free_at_least` are.
+#[derive(Debug, Clone, PartialEq)]
+struct SeatingRules {
+    neighborhood: Neighborhood,
+    occupy_at_most: usize,
+    free_at_least: usize,
+    transparent: Vec<CellState>,
+}
+
+impl SeatingRules {
+    fn for_part(part: char) -> SeatingRules {
+        let (neighborhood, free_at_least) = match part {
+            'a' => (Neighborhood::Adjacent, 4),
+            'b' => (Neighborhood::LineOfSight(None), 5),
+            _ => panic!("Only parts a and b are allowed"),
+        };
+        SeatingRules {
+            neighborhood,
+            occupy_at_most: 0,
+            free_at_least,
+            transparent: vec![CellState::Floor],
+        }
+    }
+
+    /// Reads rules from a spec of space separated `key=value` pairs, with
+    /// part a's rules for anything left out:
+    ///
+    /// - `neighbors=adjacent`, `neighbors=sight`, `neighbors=sight:3` (at
+    ///   most 3 cells away) or `neighbors=-1,0;1,0;0,2` (custom offsets)
+    /// - `occupy=0`, the most occupied neighbours a free seat fills up with
+    /// - `free=4`, the fewest occupied neighbours that empty a seat
+    /// - `transparent=.L`, the cells line of sight passes through
+    fn parse(spec: &str) -> Result<SeatingRules, String> {
+        let mut rules = SeatingRules::for_part('a');
+        for pair in spec.split_whitespace() {
+            let mut kv = pair.splitn(2, '=');
+            let key = kv.next().unwrap();
+            let value = kv
+                .next()
+                .ok_or_else(|| format!("Expected key=value, got {:?}", pair))?;
+            let bad_value = || format!("Bad value for {}: {:?}", key, value);
+            match key {
+                "neighbors" => {
+                    rules.neighborhood = match value {
+                        "adjacent" => Neighborhood::Adjacent,
+                        "sight" => Neighborhood::LineOfSight(None),
+                        _ if value.starts_with("sight:") => {
+                            let dist = value["sight:".len()..].parse().map_err(|_| bad_value())?;
+                            Neighborhood::LineOfSight(Some(dist))
+                        }
+                        _ => {
+                            let mut offsets = Vec::new();
+                            for offset in value.split(';') {
+                                let xy: Vec<&str> = offset.split(',').collect();
+                                match xy.as_slice() {
+                                    [x, y] => offsets.push((
+                                        x.parse().map_err(|_| bad_value())?,
+                                        y.parse().map_err(|_| bad_value())?,
+                                    )),
+                                    _ => return Err(bad_value()),
+                                }
+                            }
+                            Neighborhood::Custom(offsets)
+                        }
+                    }
+                }
+                "occupy" => rules.occupy_at_most = value.parse().map_err(|_| bad_value())?,
+                "free" => rules.free_at_least = value.parse().map_err(|_| bad_value())?,
+                "transparent" => {
+                    rules.transparent = value
+                        .chars()
+                        .map(parse_state)
+                        .collect::<Option<Vec<CellState>>>()
+                        .ok_or_else(bad_value)?
+                }
+                _ => return Err(format!("Unknown rule {:?}", key)),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct WorldCell {
     state: CellState,
 }
@@ -41,6 +160,28 @@ impl fmt::Display for World {
     }
 }
 
+/// Seat visibility as a compact adjacency array: the neighbours of field
+/// index `i` are `neighbors[start[i]..start[i + 1]]`.
+#[derive(Debug)]
+struct Visibility {
+    start: Vec<usize>,
+    neighbors: Vec<u32>,
+}
+
+impl Visibility {
+    fn neighbors_of(&self, idx: usize) -> &[u32] {
+        &self.neighbors[self.start[idx]..self.start[idx + 1]]
+    }
+}
+
+fn next_state(state: CellState, occupiedcount: usize, rules: &SeatingRules) -> CellState {
+    match state {
+        CellState::Free if occupiedcount <= rules.occupy_at_most => CellState::Occupied,
+        CellState::Occupied if occupiedcount >= rules.free_at_least => CellState::Free,
+        _ => state,
+    }
+}
+
 impl World {
     fn get_cell(&self, x: i64, y: i64) -> Option<&WorldCell> {
         if x < 0 || x >= self.width as i64 {
@@ -59,30 +200,47 @@ impl World {
         self.field.get(idx as usize)
     }
 
+    #[cfg(test)]
     fn get_neighbors(&self, x: usize, y: usize, part: char) -> Vec<WorldCell> {
+        self.get_neighbors_with(x, y, &SeatingRules::for_part(part))
+    }
+
+    fn get_neighbors_with(&self, x: usize, y: usize, rules: &SeatingRules) -> Vec<WorldCell> {
+        self.neighbor_indices(x, y, rules)
+            .into_iter()
+            .map(|idx| self.field[idx].clone())
+            .collect()
+    }
+
+    /// Field indices of the neighbours of the cell at `(x, y)`, seen through
+    /// the current state of the field.
+    fn neighbor_indices(&self, x: usize, y: usize, rules: &SeatingRules) -> Vec<usize> {
         let mut neighbors = Vec::new();
-        for dir in &[
-            (-1, -1),
-            (-1, 0),
-            (-1, 1),
-            (0, -1),
-            (0, 1),
-            (1, -1),
-            (1, 0),
-            (1, 1),
-        ] {
-            let maxdist: i64 = match part {
-                'a' => 1,
-                'b' => (self.width + self.height) as i64,
-                _ => panic!(),
-            };
+        let index = |x: i64, y: i64| {
+            self.get_cell(x, y)
+                .map(|c| (c, (y as usize) * self.width + (x as usize)))
+        };
+        let (dirs, maxdist): (&[(i64, i64)], i64) = match &rules.neighborhood {
+            Neighborhood::Adjacent => (&DIRECTIONS, 1),
+            // overkill on max distance, but we need to at least go far enough
+            Neighborhood::LineOfSight(None) => (&DIRECTIONS, (self.width + self.height) as i64),
+            Neighborhood::LineOfSight(Some(dist)) => (&DIRECTIONS, *dist as i64),
+            Neighborhood::Custom(offsets) => {
+                for offset in offsets {
+                    if let Some((_, idx)) = index(x as i64 + offset.0, y as i64 + offset.1) {
+                        neighbors.push(idx);
+                    }
+                }
+                return neighbors;
+            }
+        };
+        for dir in dirs {
             for dist in 1..=maxdist {
-                // overkill on max distance, but we need to at least go far enough
                 let offset: (i64, i64) = (dir.0 * dist, dir.1 * dist);
                 let cell_in_question = ((x as i64) + offset.0, (y as i64) + offset.1);
-                if let Some(c) = self.get_cell(cell_in_question.0, cell_in_question.1) {
-                    if c.state != CellState::Floor {
-                        neighbors.push(c.clone());
+                if let Some((c, idx)) = index(cell_in_question.0, cell_in_question.1) {
+                    if !rules.transparent.contains(&c.state) {
+                        neighbors.push(idx);
                         break;
                     }
                 }
@@ -91,47 +249,128 @@ impl World {
         neighbors
     }
 
-    fn step(&mut self, part: char) {
-        let mut newfield = Vec::new();
-        let neighborthresh = match part {
-            'a' => 4,
-            'b' => 5,
-            _ => panic!(),
-        };
+    /// Precomputes every seat's neighbours under `rules`. Floor never
+    /// changes, so only seats get entries. Returns `None` when a seat state
+    /// is transparent, since then what a seat can see changes as it runs.
+    fn visibility(&self, rules: &SeatingRules) -> Option<Visibility> {
+        if rules.transparent.iter().any(|s| *s != CellState::Floor) {
+            return None;
+        }
+        let mut start = Vec::with_capacity(self.field.len() + 1);
+        let mut neighbors = Vec::new();
+        start.push(0);
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if self.field[row * self.width + col].state != CellState::Floor {
+                    neighbors.extend(
+                        self.neighbor_indices(col, row, rules)
+                            .into_iter()
+                            .map(|idx| idx as u32),
+                    );
+                }
+                start.push(neighbors.len());
+            }
+        }
+        Some(Visibility { start, neighbors })
+    }
+
+    #[cfg(test)]
+    fn step(&mut self, part: char) -> usize {
+        self.step_with(&SeatingRules::for_part(part))
+    }
+
+    /// Advances one generation, returning how many cells changed.
+    fn step_with(&mut self, rules: &SeatingRules) -> usize {
+        let mut newfield = Vec::with_capacity(self.field.len());
+        let mut changed = 0;
         for row in 0..self.height {
             for col in 0..self.width {
-                let n = self.get_neighbors(col, row, part);
+                let n = self.get_neighbors_with(col, row, rules);
                 let occupiedcount = n.iter().filter(|x| x.state == CellState::Occupied).count();
-                let currentstate = self.get_cell(col as i64, row as i64).unwrap();
-                if occupiedcount == 0 && currentstate.state == CellState::Free {
-                    newfield.push(WorldCell {
-                        state: CellState::Occupied,
-                    });
-                } else if occupiedcount >= neighborthresh
-                    && currentstate.state == CellState::Occupied
-                {
-                    newfield.push(WorldCell {
-                        state: CellState::Free,
-                    })
-                } else {
-                    newfield.push(WorldCell {
-                        state: currentstate.state,
-                    })
+                let currentstate = self.get_cell(col as i64, row as i64).unwrap().state;
+                let state = next_state(currentstate, occupiedcount, rules);
+                if state != currentstate {
+                    changed += 1;
                 }
+                newfield.push(WorldCell { state });
             }
         }
         self.field = newfield;
+        changed
+    }
+
+    /// Writes the next generation into `next` using precomputed
+    /// neighbours, returning how many cells changed.
+    fn step_into(&self, vis: &Visibility, rules: &SeatingRules, next: &mut [WorldCell]) -> usize {
+        let mut changed = 0;
+        for (idx, cell) in self.field.iter().enumerate() {
+            let occupiedcount = vis
+                .neighbors_of(idx)
+                .iter()
+                .filter(|n| self.field[**n as usize].state == CellState::Occupied)
+                .count();
+            let state = next_state(cell.state, occupiedcount, rules);
+            if state != cell.state {
+                changed += 1;
+            }
+            next[idx].state = state;
+        }
+        changed
     }
 
+    /// Runs until a generation changes nothing. The puzzle's own rules
+    /// always settle, so no history is kept and the field is only ever
+    /// swapped with its back buffer.
     fn run(&mut self, part: char) {
-        loop {
-            let currstate: &[WorldCell] = &self.field.clone();
-            self.step(part);
-            let newstate: &[WorldCell] = &self.field;
+        let rules = SeatingRules::for_part(part);
+        let vis = self.visibility(&rules);
+        let mut back = self.field.clone();
+        while self.advance(vis.as_ref(), &rules, &mut back) > 0 {}
+    }
 
-            if currstate == newstate {
-                break;
+    /// Advances one generation, through `vis` when the neighbours could be
+    /// precomputed, returning how many cells changed.
+    fn advance(
+        &mut self,
+        vis: Option<&Visibility>,
+        rules: &SeatingRules,
+        back: &mut Vec<WorldCell>,
+    ) -> usize {
+        match vis {
+            Some(vis) => {
+                let changed = self.step_into(vis, rules, back);
+                std::mem::swap(&mut self.field, back);
+                changed
             }
+            None => self.step_with(rules),
+        }
+    }
+
+    fn run_with(&mut self, rules: &SeatingRules) -> Cycle {
+        self.run_observed(rules, |_| {})
+    }
+
+    /// Runs until the layout settles or starts repeating, calling `observe`
+    /// after every generation that hadn't been seen before. A layout that
+    /// settles is a cycle of period 1. Every generation is kept to catch
+    /// oscillations, so this is only for custom rules and frame recording;
+    /// the puzzle's parts go through `run`.
+    fn run_observed<F: FnMut(&World)>(&mut self, rules: &SeatingRules, mut observe: F) -> Cycle {
+        let vis = self.visibility(rules);
+        let mut back = self.field.clone();
+        let mut history = History::new(self.field.clone());
+        loop {
+            let changed = self.advance(vis.as_ref(), rules, &mut back);
+            if changed == 0 {
+                return Cycle {
+                    first_repeat: history.len(),
+                    period: 1,
+                };
+            }
+            if let Some(cycle) = history.push(self.field.clone()) {
+                return cycle;
+            }
+            observe(self);
         }
     }
 
@@ -150,12 +389,8 @@ fn make_world(lines: &[String]) -> World {
     for line in lines.iter() {
         for c in line.trim().chars() {
             let w = WorldCell {
-                state: match c {
-                    '.' => CellState::Floor,
-                    'L' => CellState::Free,
-                    '#' => CellState::Occupied,
-                    _ => panic!("Character {} is not recognized", c),
-                },
+                state: parse_state(c)
+                    .unwrap_or_else(|| panic!("Character {} is not recognized", c)),
             };
             field.push(w);
         }
@@ -179,6 +414,34 @@ fn day11b(lines: &[String]) -> i64 {
     world.count_occupied()
 }
 
+/// Occupied seats once the layout settles under rules read from `spec`, or
+/// an error if it oscillates instead.
+pub fn day11_with_rules(lines: &[String], spec: &str) -> Result<i64, String> {
+    let rules = SeatingRules::parse(spec)?;
+    let mut world = make_world(lines);
+    let cycle = world.run_with(&rules);
+    if cycle.period > 1 {
+        return Err(format!(
+            "Seating never settles: from generation {} it repeats every {} generations",
+            cycle.start(),
+            cycle.period
+        ));
+    }
+    Ok(world.count_occupied())
+}
+
+/// Every generation of the layout under a part's rules, up to the point
+/// it settles.
+pub fn day11_frames(lines: &[String], part: char) -> FrameRecorder {
+    let mut world = make_world(lines);
+    let mut recorder = FrameRecorder::new();
+    recorder.record(Frame::from_text(&world.to_string()));
+    world.run_observed(&SeatingRules::for_part(part), |w| {
+        recorder.record(Frame::from_text(&w.to_string()))
+    });
+    recorder
+}
+
 pub fn day11(lines: &[String], part: char) -> i64 {
     match part {
         'a' => day11a(lines),
@@ -263,5 +526,144 @@ mod tests {
         assert_eq!(day11::day11a(&lines), 37);
         assert_eq!(day11::day11a(&lines2), 12);
         assert_eq!(day11::day11b(&lines), 26);
+
+        let recorder = day11::day11_frames(&lines, 'a');
+        assert_eq!(recorder.frames().len(), 6);
+        assert_eq!(
+            recorder.frames()[0].to_string(),
+            day11::make_world(&lines).to_string()
+        );
+    }
+
+    #[test]
+    fn test_seating_rules() {
+        use day11::{CellState, Neighborhood, SeatingRules};
+
+        assert_eq!(
+            SeatingRules::parse("").unwrap(),
+            SeatingRules::for_part('a')
+        );
+        assert_eq!(
+            SeatingRules::parse("neighbors=sight free=5").unwrap(),
+            SeatingRules::for_part('b')
+        );
+        let rules =
+            SeatingRules::parse("neighbors=sight:2 occupy=1 free=3 transparent=.L").unwrap();
+        assert_eq!(rules.neighborhood, Neighborhood::LineOfSight(Some(2)));
+        assert_eq!(rules.occupy_at_most, 1);
+        assert_eq!(rules.free_at_least, 3);
+        assert_eq!(rules.transparent, vec![CellState::Floor, CellState::Free]);
+        assert_eq!(
+            SeatingRules::parse("neighbors=-1,0;1,0")
+                .unwrap()
+                .neighborhood,
+            Neighborhood::Custom(vec![(-1, 0), (1, 0)])
+        );
+        assert!(SeatingRules::parse("neighbors=sight:x").is_err());
+        assert!(SeatingRules::parse("neighbors=1,2,3").is_err());
+        assert!(SeatingRules::parse("free").is_err());
+        assert!(SeatingRules::parse("transparent=?").is_err());
+        assert!(SeatingRules::parse("gravity=1").is_err());
+
+        let input = "L.LL.LL.LL
+        LLLLLLL.LL
+        L.L.L..L..
+        LLLL.LL.LL
+        L.LL.LL.LL
+        L.LLLLL.LL
+        ..L.L.....
+        LLLLLLLLLL
+        L.LLLLLL.L
+        L.LLLLL.LL";
+        let lines: Vec<String> = input.split('\n').map(|x| x.to_string()).collect();
+        assert_eq!(day11::day11_with_rules(&lines, ""), Ok(37));
+        assert_eq!(
+            day11::day11_with_rules(&lines, "neighbors=sight free=5"),
+            Ok(26)
+        );
+        // Unlimited sight through the 10x10 room is the same as sight:20.
+        assert_eq!(
+            day11::day11_with_rules(&lines, "neighbors=sight:20 free=5"),
+            Ok(26)
+        );
+
+        // Seats this easily filled and emptied flip between full and empty.
+        let oscillating = SeatingRules::parse("neighbors=sight:2 occupy=1 free=3").unwrap();
+        let mut world = day11::make_world(&lines);
+        let cycle = world.run_with(&oscillating);
+        assert_eq!((cycle.first_repeat, cycle.period), (2, 2));
+        assert!(day11::day11_with_rules(&lines, "neighbors=sight:2 occupy=1 free=3").is_err());
+
+        // Left and right only: a row of seats alternates, then settles.
+        let row = vec!["LLLLL".to_string()];
+        assert_eq!(
+            day11::day11_with_rules(&row, "neighbors=-1,0;1,0 free=2"),
+            Ok(3)
+        );
+
+        let world = day11::make_world(&lines);
+        let n = world.get_neighbors_with(
+            1,
+            0,
+            &SeatingRules::parse("neighbors=0,1;9,9;-2,0").unwrap(),
+        );
+        assert_eq!(n.len(), 1);
+        assert_eq!(n[0].state, CellState::Free);
+    }
+
+    #[test]
+    fn test_visibility() {
+        use day11::SeatingRules;
+
+        let input = "L.LL.LL.LL
+        LLLLLLL.LL
+        L.L.L..L..
+        LLLL.LL.LL
+        L.LL.LL.LL
+        L.LLLLL.LL
+        ..L.L.....
+        LLLLLLLLLL
+        L.LLLLLL.L
+        L.LLLLL.LL";
+        let lines: Vec<String> = input.split('\n').map(|x| x.to_string()).collect();
+
+        let world = day11::make_world(&lines);
+        let rules = SeatingRules::for_part('b');
+        let vis = world.visibility(&rules).unwrap();
+        // (0, 0) sees down the column, along the row and diagonally.
+        assert_eq!(vis.neighbors_of(0), &[10, 2, 11]);
+        assert!(vis.neighbors_of(1).is_empty());
+        assert!(world
+            .visibility(&SeatingRules::parse("transparent=.L").unwrap())
+            .is_none());
+
+        for spec in &[
+            "",
+            "neighbors=sight free=5",
+            "neighbors=sight:2 occupy=1 free=5",
+        ] {
+            let rules = SeatingRules::parse(spec).unwrap();
+            let mut fast = day11::make_world(&lines);
+            let mut slow = day11::make_world(&lines);
+            let vis = fast.visibility(&rules).unwrap();
+            let mut back = fast.field.clone();
+            loop {
+                let changed = fast.step_into(&vis, &rules, &mut back);
+                std::mem::swap(&mut fast.field, &mut back);
+                assert_eq!(slow.step_with(&rules), changed);
+                assert_eq!(fast.field, slow.field);
+                if changed == 0 {
+                    break;
+                }
+            }
+        }
+
+        // Seeing through empty seats falls back to stepping cell by cell:
+        // the middle three empty, then the ends see each other.
+        let row = vec!["LLLLL".to_string()];
+        assert_eq!(
+            day11::day11_with_rules(&row, "neighbors=sight free=2 transparent=.L"),
+            Ok(2)
+        );
     }
 }
@@ -1,121 +1,412 @@
-#[derive(Debug)]
-struct Waypoint {
-    x: i64,
-    y: i64,
+use std::fmt;
+
+/// A displacement on a compass rose cut into bearings `base` degrees apart,
+/// measured clockwise from north. It's held exactly as a whole number of
+/// units along each bearing in the half turn [0, 180); units along the
+/// other half count negatively on the opposite bearing. With a base of 90
+/// that's just the north and east components.
+#[derive(Debug, Clone, PartialEq)]
+struct RoseVector {
+    base: i64,
+    units: Vec<i64>,
 }
 
-impl Waypoint {
-    fn rot_left(&mut self, amt: i64) {
-        let steps = amt / 90;
-        for _ in 0..steps {
-            let tmp = self.y;
-            self.y = self.x;
-            self.x = -tmp;
+impl RoseVector {
+    fn zero(base: i64) -> RoseVector {
+        RoseVector {
+            base,
+            units: vec![0; (180 / base) as usize],
+        }
+    }
+
+    /// Slot in `units` for the `k`th bearing, and the sign it's stored with.
+    fn slot(&self, k: i64) -> (usize, i64) {
+        let n = self.units.len() as i64;
+        let k = k.rem_euclid(2 * n);
+        if k < n {
+            (k as usize, 1)
+        } else {
+            ((k - n) as usize, -1)
+        }
+    }
+
+    /// Adds `amount` units along `bearing`, a multiple of the base angle.
+    fn add_along(&mut self, bearing: i64, amount: i64) {
+        let (i, sign) = self.slot(bearing / self.base);
+        self.units[i] += sign * amount;
+    }
+
+    fn add_scaled(&mut self, other: &RoseVector, times: i64) {
+        for (u, v) in self.units.iter_mut().zip(&other.units) {
+            *u += v * times;
+        }
+    }
+
+    /// Turns the vector clockwise by `degrees`, a multiple of the base angle.
+    fn rotate(&mut self, degrees: i64) {
+        let steps = degrees / self.base;
+        let mut units = vec![0; self.units.len()];
+        for (k, u) in self.units.iter().enumerate() {
+            let (i, sign) = self.slot(k as i64 + steps);
+            units[i] += sign * u;
         }
+        self.units = units;
     }
 
-    fn rot_right(&mut self, amt: i64) {
-        let steps = amt / 90;
-        for _ in 0..steps {
-            let tmp = self.y;
-            self.y = -self.x;
-            self.x = tmp;
+    /// East and north components. Bearings on the axes are exact.
+    fn xy(&self) -> (f64, f64) {
+        let mut x = 0.0;
+        let mut y = 0.0;
+        for (k, u) in self.units.iter().enumerate() {
+            let (east, north) = match k as i64 * self.base {
+                0 => (0.0, 1.0),
+                90 => (1.0, 0.0),
+                bearing => {
+                    let radians = (bearing as f64).to_radians();
+                    (radians.sin(), radians.cos())
+                }
+            };
+            x += *u as f64 * east;
+            y += *u as f64 * north;
         }
+        (x, y)
     }
 }
+
+/// What can go wrong reading or following navigation instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NavError {
+    Empty,
+    Action(char),
+    Amount(String),
+    Angle { angle: i64, base: i64 },
+    Base(i64),
+}
+
+impl fmt::Display for NavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NavError::Empty => write!(f, "Empty instruction"),
+            NavError::Action(c) => write!(f, "Unknown action {:?}", c),
+            NavError::Amount(line) => write!(f, "Bad amount in {:?}", line),
+            NavError::Angle { angle, base } => {
+                write!(f, "Can't turn {} degrees in steps of {}", angle, base)
+            }
+            NavError::Base(base) => write!(f, "Base angle {} doesn't divide 90", base),
+        }
+    }
+}
+
+impl std::error::Error for NavError {}
+
+/// Where the ship was after one instruction, and where its waypoint was
+/// relative to it.
+#[derive(Debug, Clone, PartialEq)]
+struct Position {
+    pos: RoseVector,
+    waypoint: RoseVector,
+}
+
+/// Summary of a route: the box it fits in and how far the ship sailed,
+/// measured along each straight leg.
+#[derive(Debug, Clone, PartialEq)]
+struct RouteStats {
+    min: (f64, f64),
+    max: (f64, f64),
+    distance: f64,
+}
+
 #[derive(Debug)]
 struct Ship {
+    base: i64,
     heading: i64,
-    x: i64,
-    y: i64,
-    waypoint: Waypoint,
+    pos: RoseVector,
+    waypoint: RoseVector,
+    route: Vec<Position>,
 }
 
 impl Ship {
+    #[allow(dead_code)]
     fn new() -> Ship {
-        Ship {
+        Ship::with_base(90).unwrap()
+    }
+
+    /// A ship that can turn in steps of `base` degrees, which has to divide
+    /// 90 so the compass points stay on the rose.
+    fn with_base(base: i64) -> Result<Ship, NavError> {
+        if base <= 0 || 90 % base != 0 {
+            return Err(NavError::Base(base));
+        }
+        let mut waypoint = RoseVector::zero(base);
+        waypoint.add_along(90, 10);
+        waypoint.add_along(0, 1);
+        let mut ship = Ship {
+            base,
             heading: 90,
-            x: 0,
-            y: 0,
-            waypoint: Waypoint { x: 10, y: 1 },
+            pos: RoseVector::zero(base),
+            waypoint,
+            route: Vec::new(),
+        };
+        ship.record();
+        Ok(ship)
+    }
+
+    fn record(&mut self) {
+        self.route.push(Position {
+            pos: self.pos.clone(),
+            waypoint: self.waypoint.clone(),
+        });
+    }
+
+    fn turn(&self, angle: i64) -> Result<i64, NavError> {
+        if angle % self.base != 0 {
+            return Err(NavError::Angle {
+                angle,
+                base: self.base,
+            });
         }
+        Ok(angle)
     }
 
-    fn step_a(&mut self, rule: (char, i64)) {
+    fn step_a(&mut self, rule: (char, i64)) -> Result<(), NavError> {
         match rule.0 {
-            'N' => self.y += rule.1,
-            'S' => self.y -= rule.1,
-            'E' => self.x += rule.1,
-            'W' => self.x -= rule.1,
-            'R' => self.heading += rule.1,
-            'L' => self.heading -= rule.1,
-            'F' => match self.heading {
-                0 => self.y += rule.1,
-                90 => self.x += rule.1,
-                180 => self.y -= rule.1,
-                270 => self.x -= rule.1,
-                _ => panic!("Heading not one of 0,90,180,270"),
-            },
-            _ => panic!("Unrecognized rule char"),
+            'N' => self.pos.add_along(0, rule.1),
+            'S' => self.pos.add_along(180, rule.1),
+            'E' => self.pos.add_along(90, rule.1),
+            'W' => self.pos.add_along(270, rule.1),
+            'R' => self.heading += self.turn(rule.1)?,
+            'L' => self.heading -= self.turn(rule.1)?,
+            'F' => self.pos.add_along(self.heading, rule.1),
+            c => return Err(NavError::Action(c)),
         };
         self.heading = self.heading.rem_euclid(360);
-        assert!(self.heading >= 0);
-        assert!(self.heading < 360);
+        self.record();
+        Ok(())
     }
 
-    fn step_b(&mut self, rule: (char, i64)) {
+    fn step_b(&mut self, rule: (char, i64)) -> Result<(), NavError> {
         match rule.0 {
-            'N' => self.waypoint.y += rule.1,
-            'S' => self.waypoint.y -= rule.1,
-            'E' => self.waypoint.x += rule.1,
-            'W' => self.waypoint.x -= rule.1,
-            'R' => self.waypoint.rot_right(rule.1),
-            'L' => self.waypoint.rot_left(rule.1),
-            'F' => {
-                self.x += self.waypoint.x * rule.1;
-                self.y += self.waypoint.y * rule.1;
-            }
-            _ => panic!("Unrecognized rule char"),
+            'N' => self.waypoint.add_along(0, rule.1),
+            'S' => self.waypoint.add_along(180, rule.1),
+            'E' => self.waypoint.add_along(90, rule.1),
+            'W' => self.waypoint.add_along(270, rule.1),
+            'R' => self.waypoint.rotate(self.turn(rule.1)?),
+            'L' => self.waypoint.rotate(-self.turn(rule.1)?),
+            'F' => self.pos.add_scaled(&self.waypoint, rule.1),
+            c => return Err(NavError::Action(c)),
         };
-        self.heading = self.heading.rem_euclid(360);
-        assert!(self.heading >= 0);
-        assert!(self.heading < 360);
+        self.record();
+        Ok(())
     }
 
+    /// Manhattan distance from the start, rounded when the ship has sailed
+    /// off the compass axes.
     fn manhattan_distance(&self) -> i64 {
-        self.x.abs() + self.y.abs()
+        let (x, y) = self.pos.xy();
+        (x.abs() + y.abs()).round() as i64
+    }
+
+    fn stats(&self) -> RouteStats {
+        let points: Vec<(f64, f64)> = self.route.iter().map(|p| p.pos.xy()).collect();
+        let mut min = points[0];
+        let mut max = points[0];
+        for (x, y) in &points {
+            min = (min.0.min(*x), min.1.min(*y));
+            max = (max.0.max(*x), max.1.max(*y));
+        }
+        let distance = points
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1))
+            .sum();
+        RouteStats { min, max, distance }
+    }
+
+    /// One line per instruction: the position after it, then the waypoint.
+    fn route_text(&self) -> String {
+        let mut text = String::new();
+        for (i, p) in self.route.iter().enumerate() {
+            let (x, y) = p.pos.xy();
+            let (wx, wy) = p.waypoint.xy();
+            text += &format!("{} {} {} {} {}\n", i, x, y, wx, wy);
+        }
+        text
+    }
+
+    /// SVG drawing of the route with north at the top, optionally with an
+    /// arrow from each position to its waypoint.
+    fn route_svg(&self, waypoints: bool) -> String {
+        // SVG's y axis points south. Subtracting from zero keeps a north
+        // of 0 from being written as -0.
+        let flip = |y: f64| 0.0 - y;
+        let stats = self.stats();
+        let mut min = stats.min;
+        let mut max = stats.max;
+        if waypoints {
+            for p in &self.route {
+                let (x, y) = p.pos.xy();
+                let (wx, wy) = p.waypoint.xy();
+                min = (min.0.min(x + wx), min.1.min(y + wy));
+                max = (max.0.max(x + wx), max.1.max(y + wy));
+            }
+        }
+        let margin = 1.0 + ((max.0 - min.0).max(max.1 - min.1) / 100.0).floor();
+        let points: Vec<String> = self
+            .route
+            .iter()
+            .map(|p| {
+                let (x, y) = p.pos.xy();
+                format!("{},{}", x, flip(y))
+            })
+            .collect();
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            min.0 - margin,
+            flip(max.1) - margin,
+            max.0 - min.0 + 2.0 * margin,
+            max.1 - min.1 + 2.0 * margin
+        );
+        svg += &format!("  <g fill=\"none\" stroke-width=\"{}\">\n", margin);
+        if waypoints {
+            for p in &self.route {
+                let (x, y) = p.pos.xy();
+                let (wx, wy) = p.waypoint.xy();
+                svg += &format!(
+                    "    <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"orange\" />\n",
+                    x,
+                    flip(y),
+                    x + wx,
+                    flip(y + wy)
+                );
+            }
+        }
+        svg += &format!(
+            "    <polyline points=\"{}\" stroke=\"navy\" />\n",
+            points.join(" ")
+        );
+        svg += "  </g>\n</svg>\n";
+        svg
+    }
+}
+
+fn parse_line(line: &str) -> Result<(char, i64), NavError> {
+    let mut chars = line.chars();
+    let letter = chars.next().ok_or(NavError::Empty)?;
+    if !"NSEWLRF".contains(letter) {
+        return Err(NavError::Action(letter));
+    }
+    let num = chars
+        .as_str()
+        .parse::<i64>()
+        .map_err(|_| NavError::Amount(line.to_string()))?;
+    Ok((letter, num))
+}
+
+/// Follows the instructions in `lines` with a ship turning in steps of
+/// `base` degrees, using part a's or part b's rules.
+fn sail(lines: &[String], part: char, base: i64) -> Result<Ship, NavError> {
+    let mut ship = Ship::with_base(base)?;
+    for line in lines.iter().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        let rule = parse_line(line)?;
+        match part {
+            'a' => ship.step_a(rule)?,
+            _ => ship.step_b(rule)?,
+        }
     }
+    Ok(ship)
 }
 
-fn parse_line(line: &str) -> (char, i64) {
-    let letter = line.chars().next().unwrap();
-    let num = line[1..].parse::<i64>().unwrap();
-    (letter, num)
+/// One instruction moving `amount` along an axis: `pos` if it's positive,
+/// `neg` if it's negative, and nothing at all if it's zero.
+fn axis_move(amount: i64, pos: char, neg: char) -> Option<(char, i64)> {
+    match amount {
+        0 => None,
+        a if a > 0 => Some((pos, a)),
+        a => Some((neg, -a)),
+    }
 }
 
-fn day12a(lines: &[String]) -> i64 {
-    let mut ship = Ship::new();
-    let rules: Vec<(char, i64)> = lines.iter().map(|x| parse_line(x.trim())).collect();
-    for rule in rules {
-        ship.step_a(rule);
+/// The shortest list of instructions, with positive amounts, that takes a
+/// new ship to `target` under part a's or part b's rules, and for part a
+/// leaves it facing `heading` if one is given. Headings have to be on the
+/// compass points, and part b never turns the ship itself, so it can only
+/// be asked for the starting heading of 90.
+#[allow(dead_code)]
+fn plan(target: (i64, i64), heading: Option<i64>, part: char) -> Option<Vec<(char, i64)>> {
+    let turn = match heading.map(|h| h.rem_euclid(360)) {
+        None | Some(90) => None,
+        Some(h) if h % 90 != 0 => return None,
+        Some(_) if part != 'a' => return None,
+        Some(h) => match (h - 90).rem_euclid(360) {
+            270 => Some(('L', 90)),
+            diff => Some(('R', diff)),
+        },
+    };
+    let (x, y) = target;
+    if part == 'a' {
+        let mut plan: Vec<(char, i64)> = turn.into_iter().collect();
+        plan.extend(axis_move(x, 'E', 'W'));
+        plan.extend(axis_move(y, 'N', 'S'));
+        return Some(plan);
+    }
+
+    if target == (0, 0) {
+        return Some(vec![]);
     }
-    ship.manhattan_distance()
+    // Every F moves by a whole number of waypoints, so look for a single
+    // change to the waypoint that makes the target a multiple of it.
+    let (wx, wy) = (10, 1);
+    let mut waypoints = vec![
+        (vec![], (wx, wy)),
+        (vec![('R', 90)], (wy, -wx)),
+        (vec![('R', 180)], (-wx, -wy)),
+        (vec![('L', 90)], (-wy, wx)),
+    ];
+    if x % wx == 0 && x / wx > 0 && y % (x / wx) == 0 {
+        let k = x / wx;
+        waypoints.extend(axis_move(y / k - wy, 'N', 'S').map(|m| (vec![m], (wx, y / k))));
+    }
+    if y % wy == 0 && y / wy > 0 && x % (y / wy) == 0 {
+        let k = y / wy;
+        waypoints.extend(axis_move(x / k - wx, 'E', 'W').map(|m| (vec![m], (x / k, wy))));
+    }
+    for (mut plan, (wx, wy)) in waypoints {
+        let k = if wx != 0 { x / wx } else { y / wy };
+        if k > 0 && (k * wx, k * wy) == target {
+            plan.push(('F', k));
+            return Some(plan);
+        }
+    }
+    // Otherwise move the waypoint onto the target and sail to it once.
+    let mut plan: Vec<(char, i64)> = Vec::new();
+    plan.extend(axis_move(x - wx, 'E', 'W'));
+    plan.extend(axis_move(y - wy, 'N', 'S'));
+    plan.push(('F', 1));
+    Some(plan)
+}
+
+fn day12a(lines: &[String]) -> Result<i64, NavError> {
+    Ok(sail(lines, 'a', 90)?.manhattan_distance())
+}
+
+fn day12b(lines: &[String]) -> Result<i64, NavError> {
+    Ok(sail(lines, 'b', 90)?.manhattan_distance())
 }
 
-fn day12b(lines: &[String]) -> i64 {
-    let mut ship = Ship::new();
-    let rules: Vec<(char, i64)> = lines.iter().map(|x| parse_line(x.trim())).collect();
-    for rule in rules {
-        ship.step_b(rule);
+/// The route sailed in a part, as SVG or as plain text.
+pub fn day12_route(lines: &[String], part: char, svg: bool) -> Result<String, NavError> {
+    let ship = sail(lines, part, 90)?;
+    if svg {
+        Ok(ship.route_svg(part == 'b'))
+    } else {
+        Ok(ship.route_text())
     }
-    ship.manhattan_distance()
 }
 
-pub fn day12(lines: &[String], part: char) -> i64 {
+pub fn day12(lines: &[String], part: char) -> Result<i64, NavError> {
     match part {
         'a' => day12a(lines),
         'b' => day12b(lines),
-        _ => 0,
+        _ => Ok(0),
     }
 }
 
@@ -131,27 +422,178 @@ mod tests {
         R90
         F11";
         let lines: Vec<String> = input.split('\n').map(|x| x.trim().to_string()).collect();
-        let rules: Vec<(char, i64)> = lines.iter().map(|x| day12::parse_line(x)).collect();
+        let rules: Vec<(char, i64)> = lines
+            .iter()
+            .map(|x| day12::parse_line(x).unwrap())
+            .collect();
         //println!("{:?}", rules);
         let mut ship = day12::Ship::new();
         for rule in &rules {
-            ship.step_a(*rule);
+            ship.step_a(*rule).unwrap();
             //println!("{:?}", ship);
         }
         assert_eq!(ship.manhattan_distance(), 25);
 
         let mut ship2 = day12::Ship::new();
         for rule in &rules {
-            ship2.step_b(*rule);
+            ship2.step_b(*rule).unwrap();
         }
         assert_eq!(ship2.manhattan_distance(), 286);
     }
 
+    #[test]
+    fn test_route() {
+        let lines: Vec<String> = vec!["F10", "N3", "F7", "R90", "F11"]
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+
+        assert_eq!(day12::day12(&lines, 'a'), Ok(25));
+        assert_eq!(day12::day12(&lines, 'b'), Ok(286));
+        let bad: Vec<String> = vec!["F10".to_string(), "X3".to_string()];
+        assert_eq!(day12::day12(&bad, 'b'), Err(day12::NavError::Action('X')));
+
+        let ship = day12::sail(&lines, 'a', 90).unwrap();
+        let visited: Vec<(f64, f64)> = ship.route.iter().map(|p| p.pos.xy()).collect();
+        assert_eq!(
+            visited,
+            vec![
+                (0.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 3.0),
+                (17.0, 3.0),
+                (17.0, 3.0),
+                (17.0, -8.0)
+            ]
+        );
+        let stats = ship.stats();
+        assert_eq!((stats.min, stats.max), ((0.0, -8.0), (17.0, 3.0)));
+        assert!((stats.distance - 31.0).abs() < 1e-9);
+
+        let ship = day12::sail(&lines, 'b', 90).unwrap();
+        assert_eq!(ship.route[4].waypoint.xy(), (4.0, -10.0));
+        assert_eq!(ship.route[5].pos.xy(), (214.0, -72.0));
+        let text = day12::day12_route(&lines, 'b', false).unwrap();
+        assert_eq!(text.lines().next(), Some("0 0 0 10 1"));
+        assert_eq!(text.lines().last(), Some("5 214 -72 4 -10"));
+
+        let svg = day12::day12_route(&lines, 'b', true).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("points=\"0,0 100,-10 100,-10 170,-38 170,-38 214,72\""));
+        assert_eq!(svg.matches("<line").count(), 6);
+        assert_eq!(
+            day12::day12_route(&lines, 'a', true)
+                .unwrap()
+                .matches("<line")
+                .count(),
+            0
+        );
+    }
+
     #[test]
     fn test_waypoint_rotate() {
-        let mut w = day12::Waypoint { x: 1, y: 1 };
-        w.rot_left(90);
-        assert_eq!(w.x, -1);
-        assert_eq!(w.y, 1);
+        let mut w = day12::RoseVector::zero(90);
+        w.add_along(90, 1);
+        w.add_along(0, 1);
+        w.rotate(-90);
+        assert_eq!(w.xy(), (-1.0, 1.0));
+    }
+
+    #[test]
+    fn test_plan() {
+        use proptest::prelude::*;
+
+        let lines = |plan: &[(char, i64)]| -> Vec<String> {
+            plan.iter().map(|(c, n)| format!("{}{}", c, n)).collect()
+        };
+        assert_eq!(day12::plan((100, 10), None, 'b'), Some(vec![('F', 10)]));
+        assert_eq!(
+            day12::plan((3, -30), None, 'b'),
+            Some(vec![('R', 90), ('F', 3)])
+        );
+        assert_eq!(
+            day12::plan((20, 4), None, 'b'),
+            Some(vec![('N', 1), ('F', 2)])
+        );
+        assert_eq!(
+            day12::plan((7, 5), None, 'b'),
+            Some(vec![('W', 3), ('N', 4), ('F', 1)])
+        );
+        assert_eq!(
+            day12::plan((-4, 0), Some(0), 'a'),
+            Some(vec![('L', 90), ('W', 4)])
+        );
+        assert_eq!(day12::plan((1, 1), Some(45), 'a'), None);
+        assert_eq!(day12::plan((1, 1), Some(180), 'b'), None);
+
+        proptest!(|(x in -300i64..300, y in -300i64..300, h in 0i64..5)| {
+            let heading = if h == 4 { None } else { Some(h * 90 - 360) };
+            let plan = day12::plan((x, y), heading, 'a').unwrap();
+            let ship = day12::sail(&lines(&plan), 'a', 90).unwrap();
+            prop_assert_eq!(ship.pos.xy(), (x as f64, y as f64));
+            prop_assert_eq!(ship.heading, heading.unwrap_or(90).rem_euclid(360));
+            let needed = (x != 0) as usize
+                + (y != 0) as usize
+                + matches!(heading, Some(h) if h.rem_euclid(360) != 90) as usize;
+            prop_assert_eq!(plan.len(), needed);
+
+            let plan = day12::plan((x, y), None, 'b').unwrap();
+            let ship = day12::sail(&lines(&plan), 'b', 90).unwrap();
+            prop_assert_eq!(ship.pos.xy(), (x as f64, y as f64));
+            prop_assert!(plan.len() <= 3);
+            prop_assert!(plan.iter().all(|(_, n)| *n > 0));
+        });
+    }
+
+    #[test]
+    fn test_rose_rotation() {
+        use day12::{NavError, RoseVector};
+
+        // Eight turns of 45 degrees, or any whole number of turns, leave
+        // the vector exactly where it was.
+        let mut w = RoseVector::zero(45);
+        w.add_along(90, 10);
+        w.add_along(0, 1);
+        let start = w.clone();
+        for _ in 0..8 {
+            w.rotate(45);
+        }
+        assert_eq!(w, start);
+        w.rotate(-1080);
+        assert_eq!(w, start);
+        w.rotate(135);
+        w.rotate(-45);
+        let mut right = RoseVector::zero(90);
+        right.add_along(90, 10);
+        right.add_along(0, 1);
+        right.rotate(90);
+        assert_eq!(w.xy(), right.xy());
+
+        let lines: Vec<String> = vec!["R45", "F10", "L450", "F10"]
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+        let ship = day12::sail(&lines, 'a', 45).unwrap();
+        assert_eq!(ship.heading, 45);
+        let (x, y) = ship.pos.xy();
+        let half = 10.0 * std::f64::consts::FRAC_1_SQRT_2;
+        assert!((x - 2.0 * half).abs() < 1e-9 && y.abs() < 1e-9);
+        assert_eq!(ship.manhattan_distance(), 14);
+        assert_eq!(
+            day12::sail(&lines, 'a', 90).unwrap_err(),
+            NavError::Angle {
+                angle: 45,
+                base: 90
+            }
+        );
+
+        assert_eq!(day12::parse_line("X10"), Err(NavError::Action('X')));
+        assert_eq!(
+            day12::parse_line("F"),
+            Err(NavError::Amount("F".to_string()))
+        );
+        assert_eq!(day12::parse_line(""), Err(NavError::Empty));
+        assert!(day12::Ship::with_base(7).is_err());
+        assert!(day12::Ship::with_base(0).is_err());
     }
 }
@@ -1,3 +1,9 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use num::{BigInt, CheckedAdd, CheckedMul, Integer, ToPrimitive, Zero};
+
 fn parse_lines(lines: &[String]) -> (i64, Vec<Option<i64>>) {
     let eta: i64 = lines[0].parse::<i64>().unwrap();
     let buslist: Vec<Option<i64>> = lines[1]
@@ -26,50 +32,270 @@ fn day13a(lines: &[String]) -> i64 {
     busid * wait
 }
 
+/// The solutions of `x = residue (mod modulus)`, with the residue kept in
+/// `0..modulus`.
+#[derive(Debug, Clone, PartialEq)]
+struct Congruence<T> {
+    residue: T,
+    modulus: T,
+}
+
+impl<T: Integer + Clone + fmt::Display> Congruence<T> {
+    fn new(residue: T, modulus: T) -> Result<Congruence<T>, CongruenceError> {
+        if modulus <= T::zero() {
+            return Err(CongruenceError::BadModulus(modulus.to_string()));
+        }
+        Ok(Congruence {
+            residue: residue.mod_floor(&modulus),
+            modulus,
+        })
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Congruence<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "x = {} (mod {})", self.residue, self.modulus)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CongruenceError {
+    /// Moduli have to be positive.
+    BadModulus(String),
+    /// The first congruence, which is everything merged so far, and the
+    /// second have no solution in common.
+    Inconsistent(String, String),
+    /// The numbers got too big for the integer type being used.
+    Overflow,
+}
+
+impl fmt::Display for CongruenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CongruenceError::BadModulus(m) => write!(f, "Modulus {} is not positive", m),
+            CongruenceError::Inconsistent(known, next) => {
+                write!(f, "No x satisfies both {} and {}", known, next)
+            }
+            CongruenceError::Overflow => write!(f, "Arithmetic overflow"),
+        }
+    }
+}
+
+impl std::error::Error for CongruenceError {}
+
 #[allow(clippy::many_single_char_names)]
-fn egcd(a: i64, b: i64) -> (i64, i64, i64) {
-    if a == 0 {
-        (b, 0, 1)
+fn egcd<T: Integer + Clone>(a: T, b: T) -> (T, T, T) {
+    if a.is_zero() {
+        (b, T::zero(), T::one())
     } else {
-        let (g, x, y) = egcd(b % a, a);
-        (g, y - (b / a) * x, x)
+        let (g, x, y) = egcd(b.clone() % a.clone(), a.clone());
+        (g, y - (b / a) * x.clone(), x)
     }
 }
 
-fn mod_inv(x: i64, n: i64) -> Option<i64> {
-    let (g, x, _) = egcd(x, n);
-    if g == 1 {
-        Some((x % n + n) % n)
-    } else {
-        None
+/// Combines two congruences into one with the lcm of their moduli. This
+/// works whether or not the moduli are coprime, as long as the residues
+/// agree modulo the gcd.
+fn merge<T>(a: &Congruence<T>, b: &Congruence<T>) -> Result<Congruence<T>, CongruenceError>
+where
+    T: Integer + Clone + CheckedAdd + CheckedMul + fmt::Display,
+{
+    let overflow = || CongruenceError::Overflow;
+    let (g, p, _) = egcd(a.modulus.clone(), b.modulus.clone());
+    let diff = b.residue.clone() - a.residue.clone();
+    if !(diff.clone() % g.clone()).is_zero() {
+        return Err(CongruenceError::Inconsistent(a.to_string(), b.to_string()));
     }
+    // p * a.modulus = g (mod b.modulus), so stepping a.residue by t moduli
+    // with t = diff / g * p lands on b.residue. Reducing t first keeps the
+    // result below the lcm.
+    let step = b.modulus.clone() / g.clone();
+    let t = (diff / g.clone())
+        .mod_floor(&step)
+        .checked_mul(&p.mod_floor(&step))
+        .ok_or_else(overflow)?
+        .mod_floor(&step);
+    let modulus = (a.modulus.clone() / g)
+        .checked_mul(&b.modulus)
+        .ok_or_else(overflow)?;
+    let residue = t
+        .checked_mul(&a.modulus)
+        .ok_or_else(overflow)?
+        .checked_add(&a.residue)
+        .ok_or_else(overflow)?;
+    Ok(Congruence { residue, modulus })
 }
 
-fn chinese_remainder(residues: &[i64], modulii: &[i64]) -> Option<i64> {
-    let prod = modulii.iter().product::<i64>();
+/// Every x satisfying all of the congruences, as one congruence.
+fn solve<T>(system: &[Congruence<T>]) -> Result<Congruence<T>, CongruenceError>
+where
+    T: Integer + Clone + CheckedAdd + CheckedMul + fmt::Display,
+{
+    let mut merged = Congruence {
+        residue: T::zero(),
+        modulus: T::one(),
+    };
+    for c in system {
+        merged = merge(&merged, c)?;
+    }
+    Ok(merged)
+}
 
-    let mut sum = 0;
+/// Solves x = residues[i] (mod modulii[i]) for every i. The moduli needn't
+/// be coprime. The work is done in i64 unless that overflows, in which
+/// case it's redone with big integers.
+fn chinese_remainder(
+    residues: &[i64],
+    modulii: &[i64],
+) -> Result<Congruence<BigInt>, CongruenceError> {
+    let system = residues
+        .iter()
+        .zip(modulii)
+        .map(|(r, m)| Congruence::new(*r, *m))
+        .collect::<Result<Vec<Congruence<i64>>, CongruenceError>>()?;
+    match solve(&system) {
+        Ok(c) => Ok(Congruence {
+            residue: BigInt::from(c.residue),
+            modulus: BigInt::from(c.modulus),
+        }),
+        Err(CongruenceError::Overflow) => {
+            let system: Vec<Congruence<BigInt>> = system
+                .iter()
+                .map(|c| Congruence {
+                    residue: BigInt::from(c.residue),
+                    modulus: BigInt::from(c.modulus),
+                })
+                .collect();
+            solve(&system)
+        }
+        Err(e) => Err(e),
+    }
+}
 
-    for (&residue, &modulus) in residues.iter().zip(modulii) {
-        let p = prod / modulus;
-        sum += residue * mod_inv(p, modulus)? * p
+impl Congruence<BigInt> {
+    /// The smallest solution that's at least `t`.
+    fn first_at_or_after(&self, t: &BigInt) -> BigInt {
+        let behind = (t - &self.residue).mod_floor(&self.modulus);
+        if behind.is_zero() {
+            t.clone()
+        } else {
+            t + &self.modulus - behind
+        }
     }
+}
 
-    Some(sum % prod)
+#[derive(Debug, Clone, PartialEq)]
+enum TimetableError {
+    UnknownBus(i64),
+    NoSolution(CongruenceError),
 }
 
-fn day13b(lines: &[String]) -> i64 {
-    let (_, buslist) = parse_lines(lines);
-    let mut residues: Vec<i64> = Vec::new();
-    let mut modulii: Vec<i64> = Vec::new();
-    for (i, opt_bus) in buslist.iter().enumerate() {
-        if let Some(bus) = opt_bus {
-            residues.push(bus - i as i64);
-            modulii.push(*bus);
+impl fmt::Display for TimetableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimetableError::UnknownBus(bus) => write!(f, "Bus {} is not in the timetable", bus),
+            TimetableError::NoSolution(e) => write!(f, "No timestamp fits: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TimetableError {}
+
+impl From<CongruenceError> for TimetableError {
+    fn from(e: CongruenceError) -> TimetableError {
+        TimetableError::NoSolution(e)
+    }
+}
+
+/// The buses in service, each leaving the station at every multiple of its
+/// id, in the order the notes list them (`None` for an "x").
+#[derive(Debug, Clone)]
+struct Timetable {
+    slots: Vec<Option<i64>>,
+}
+
+impl Timetable {
+    fn new(buslist: &[Option<i64>]) -> Timetable {
+        Timetable {
+            slots: buslist.to_vec(),
+        }
+    }
+
+    fn buses(&self) -> impl Iterator<Item = i64> + '_ {
+        self.slots.iter().flatten().copied()
+    }
+
+    fn check(&self, bus: i64) -> Result<i64, TimetableError> {
+        if self.buses().any(|b| b == bus) {
+            Ok(bus)
+        } else {
+            Err(TimetableError::UnknownBus(bus))
+        }
+    }
+
+    /// The next `n` departures at or after time `t`, as (time, bus) in time
+    /// order, with buses leaving together ordered by id.
+    #[allow(dead_code)]
+    fn next_departures(&self, t: i64, n: usize) -> Vec<(i64, i64)> {
+        let mut heap: BinaryHeap<Reverse<(i64, i64)>> = self
+            .buses()
+            .map(|bus| Reverse((t + (bus - t.rem_euclid(bus)) % bus, bus)))
+            .collect();
+        let mut departures = Vec::new();
+        while departures.len() < n {
+            match heap.pop() {
+                Some(Reverse((time, bus))) => {
+                    departures.push((time, bus));
+                    heap.push(Reverse((time + bus, bus)));
+                }
+                None => break,
+            }
+        }
+        departures
+    }
+
+    /// The first time at or after `t` when every bus in `subset` leaves at
+    /// once.
+    #[allow(dead_code)]
+    fn simultaneous(&self, subset: &[i64], t: i64) -> Result<BigInt, TimetableError> {
+        let modulii = subset
+            .iter()
+            .map(|bus| self.check(*bus))
+            .collect::<Result<Vec<i64>, TimetableError>>()?;
+        let together = chinese_remainder(&vec![0; modulii.len()], &modulii)?;
+        Ok(together.first_at_or_after(&BigInt::from(t)))
+    }
+
+    /// Each bus paired with its position in the notes, which is the offset
+    /// pattern part b asks about.
+    fn offsets(&self) -> Vec<(i64, i64)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, bus)| bus.map(|b| (b, i as i64)))
+            .collect()
+    }
+
+    /// The earliest non-negative time `t` where each `(bus, offset)` in the
+    /// pattern has that bus leaving at `t + offset`.
+    fn earliest_with_offsets(&self, pattern: &[(i64, i64)]) -> Result<BigInt, TimetableError> {
+        let mut residues = Vec::new();
+        let mut modulii = Vec::new();
+        for (bus, offset) in pattern {
+            modulii.push(self.check(*bus)?);
+            residues.push(-offset);
         }
+        Ok(chinese_remainder(&residues, &modulii)?.residue)
     }
-    let c = chinese_remainder(&residues, &modulii);
-    c.unwrap()
+}
+
+fn day13b(lines: &[String]) -> i64 {
+    let (_, buslist) = parse_lines(lines);
+    let timetable = Timetable::new(&buslist);
+    let t = timetable
+        .earliest_with_offsets(&timetable.offsets())
+        .unwrap_or_else(|e| panic!("{}", e));
+    t.to_i64().unwrap()
 }
 
 pub fn day13(lines: &[String], part: char) -> i64 {
@@ -104,4 +330,90 @@ mod tests {
         assert_eq!(day13::day13b(&l3), 754018);
         assert_eq!(day13::day13b(&lines), 1068781);
     }
+
+    #[test]
+    fn test_timetable() {
+        use day13::{CongruenceError, Timetable, TimetableError};
+        use num::BigInt;
+
+        let lines: Vec<String> = vec!["939".to_string(), "7,13,x,x,59,x,31,19".to_string()];
+        let (_, buslist) = day13::parse_lines(&lines);
+        let timetable = Timetable::new(&buslist);
+
+        assert_eq!(
+            timetable.next_departures(939, 4),
+            vec![(944, 59), (945, 7), (949, 13), (950, 19)]
+        );
+        assert_eq!(timetable.next_departures(0, 2), vec![(0, 7), (0, 13)]);
+        assert_eq!(Timetable::new(&[]).next_departures(0, 2), vec![]);
+
+        assert_eq!(timetable.simultaneous(&[7, 13], 0), Ok(BigInt::from(0)));
+        assert_eq!(timetable.simultaneous(&[7, 13], 1), Ok(BigInt::from(91)));
+        assert_eq!(
+            timetable.simultaneous(&[7, 59], 1000),
+            Ok(BigInt::from(1239))
+        );
+        assert_eq!(
+            timetable.simultaneous(&[7, 5], 0),
+            Err(TimetableError::UnknownBus(5))
+        );
+
+        assert_eq!(
+            timetable.earliest_with_offsets(&timetable.offsets()),
+            Ok(BigInt::from(1068781))
+        );
+        assert_eq!(
+            timetable.earliest_with_offsets(&[(7, 0), (13, 1)]),
+            Ok(BigInt::from(77))
+        );
+        assert_eq!(
+            timetable.earliest_with_offsets(&[(7, 0), (7, 1)]),
+            Err(TimetableError::NoSolution(CongruenceError::Inconsistent(
+                "x = 0 (mod 7)".to_string(),
+                "x = 6 (mod 7)".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_congruences() {
+        use day13::{chinese_remainder, Congruence, CongruenceError};
+        use num::BigInt;
+
+        let solution = |r: i64, m: i64| {
+            Ok(Congruence {
+                residue: BigInt::from(r),
+                modulus: BigInt::from(m),
+            })
+        };
+        assert_eq!(chinese_remainder(&[2, 3], &[3, 5]), solution(8, 15));
+        // 4 and 6 share a factor of 2, and both residues are even.
+        assert_eq!(chinese_remainder(&[2, 4], &[4, 6]), solution(10, 12));
+        assert_eq!(chinese_remainder(&[-1, 5, 0], &[4, 6, 1]), solution(11, 12));
+        assert_eq!(chinese_remainder(&[], &[]), solution(0, 1));
+
+        let err = chinese_remainder(&[1, 2], &[4, 6]).unwrap_err();
+        assert_eq!(
+            err,
+            CongruenceError::Inconsistent("x = 1 (mod 4)".to_string(), "x = 2 (mod 6)".to_string())
+        );
+        assert_eq!(
+            err.to_string(),
+            "No x satisfies both x = 1 (mod 4) and x = 2 (mod 6)"
+        );
+        assert_eq!(
+            chinese_remainder(&[1], &[0]),
+            Err(CongruenceError::BadModulus("0".to_string()))
+        );
+
+        // The product of these is about 10^27, far past i64.
+        let modulii = [1_000_000_007, 998_244_353, 1_000_000_009];
+        let residues = [5, 17, 123_456_789];
+        let c = chinese_remainder(&residues, &modulii).unwrap();
+        let product: BigInt = modulii.iter().map(|m| BigInt::from(*m)).product();
+        assert_eq!(c.modulus, product);
+        for (r, m) in residues.iter().zip(&modulii) {
+            assert_eq!(&c.residue % BigInt::from(*m), BigInt::from(*r));
+        }
+    }
 }
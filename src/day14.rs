@@ -1,186 +1,280 @@
-use std::{
-    collections::{HashMap, VecDeque},
-    ops::Index,
-};
+use std::{collections::HashMap, fmt};
 
-use regex::Regex;
+/// The bits of `mask` whose character passes `pred`, most significant
+/// first.
+fn mask_bits<F: Fn(char) -> bool>(mask: &str, pred: F) -> u64 {
+    mask.chars()
+        .fold(0, |bits, c| (bits << 1) | (pred(c) as u64))
+}
 
-struct Memtape {
-    memory: HashMap<i64, i64>,
+fn day14a(lines: &[String]) -> i64 {
+    load_program(lines).run_a() as i64
 }
 
-impl Memtape {
-    fn new() -> Self {
-        Self {
-            memory: HashMap::new(),
+/// A set of addresses written as a ternary pattern: bits in `floating` can
+/// take either value, and every other bit has to match `fixed`, which is
+/// zero wherever the pattern floats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AddressPattern {
+    fixed: u64,
+    floating: u64,
+}
+
+impl AddressPattern {
+    fn len(&self) -> u128 {
+        1 << self.floating.count_ones()
+    }
+
+    fn intersect(&self, other: &AddressPattern) -> Option<AddressPattern> {
+        let both_fixed = !self.floating & !other.floating;
+        if (self.fixed ^ other.fixed) & both_fixed != 0 {
+            return None;
         }
+        let floating = self.floating & other.floating;
+        Some(AddressPattern {
+            fixed: (self.fixed | other.fixed) & !floating,
+            floating,
+        })
     }
 
-    fn sum(&self) -> i64 {
-        let mut sum = 0;
-        for v in self.memory.values() {
-            sum += v;
+    /// The addresses in `self` but not in `other`, as disjoint patterns.
+    /// Each bit that floats here but is fixed in `other` splits off the
+    /// half that disagrees with `other`; what's left once they're all fixed
+    /// is the overlap, which is dropped.
+    fn subtract(&self, other: &AddressPattern) -> Vec<AddressPattern> {
+        if self.intersect(other).is_none() {
+            return vec![*self];
+        }
+        let mut rest = Vec::new();
+        let mut current = *self;
+        let mut split = self.floating & !other.floating;
+        while split != 0 {
+            let bit = split & split.wrapping_neg();
+            split &= split - 1;
+            current.floating &= !bit;
+            rest.push(AddressPattern {
+                fixed: current.fixed | (bit & !other.fixed),
+                floating: current.floating,
+            });
+            current.fixed |= bit & other.fixed;
         }
-        sum
+        rest
     }
 }
 
-impl Index<i64> for Memtape {
-    type Output = i64;
-
-    fn index(&self, idx: i64) -> &Self::Output {
-        self.memory.get(&idx).unwrap_or(&0)
+/// Sum of memory after every write, without visiting the addresses one by
+/// one. Going backwards, each write only counts on the addresses no later
+/// write has covered, which are kept as disjoint patterns.
+fn floating_sum(writes: &[(AddressPattern, u64)]) -> u128 {
+    let mut covered: Vec<AddressPattern> = Vec::new();
+    let mut sum = 0;
+    for (pattern, value) in writes.iter().rev() {
+        let mut pieces = vec![*pattern];
+        for c in &covered {
+            pieces = pieces.iter().flat_map(|p| p.subtract(c)).collect();
+        }
+        let count: u128 = pieces.iter().map(|p| p.len()).sum();
+        sum += *value as u128 * count;
+        covered.extend(pieces);
     }
+    sum
 }
 
-#[derive(Debug, PartialEq, Eq)]
-struct Section {
-    mask: String,
-    rules: Vec<(i64, i64)>,
+/// A mask as bit sets: bits it forces to 1, bits part a leaves alone
+/// (everything not forced to 0), and floating bits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Mask {
+    or: u64,
+    and: u64,
+    floating: u64,
 }
 
-fn make_or_mask(mask: &str) -> i64 {
-    let mut newchars: Vec<char> = Vec::new();
-    for c in mask.chars() {
-        newchars.push(match c {
-            '1' => '1',
-            _ => '0',
-        });
+impl Mask {
+    /// Reads the characters of a `width`-bit mask line.
+    fn parse(mask: &str, width: u32) -> Result<Mask, String> {
+        if let Some(c) = mask.chars().find(|c| !"01X".contains(*c)) {
+            return Err(format!("Bad mask character {:?}", c));
+        }
+        if mask.len() != width as usize {
+            return Err(format!("Mask is {} bits, expected {}", mask.len(), width));
+        }
+        Ok(Mask {
+            or: mask_bits(mask, |c| c == '1'),
+            and: mask_bits(mask, |c| c != '0'),
+            floating: mask_bits(mask, |c| c == 'X'),
+        })
     }
-    let newstr: String = newchars.iter().collect();
-    i64::from_str_radix(&newstr, 2).unwrap()
-}
 
-fn make_and_mask(mask: &str) -> i64 {
-    let mut newchars: Vec<char> = Vec::new();
-    for c in mask.chars() {
-        newchars.push(match c {
-            '0' => '0',
-            _ => '1',
-        });
+    fn apply(&self, value: u64) -> u64 {
+        self.and & (self.or | value)
+    }
+
+    fn addresses(&self, address: u64) -> AddressPattern {
+        AddressPattern {
+            fixed: (address | self.or) & !self.floating,
+            floating: self.floating,
+        }
     }
-    let newstr: String = newchars.iter().collect();
-    i64::from_str_radix(&newstr, 2).unwrap()
 }
 
-fn apply_mask_to_num(mask: &str, num: i64) -> i64 {
-    let ormask = make_or_mask(mask);
-    let andmask = make_and_mask(mask);
-    andmask & (ormask | num)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Instruction {
+    SetMask(Mask),
+    Write { address: u64, value: u64 },
 }
 
-fn parse_mask(line: &str) -> String {
-    let re = Regex::new(r"^mask = (?P<mask>.*)$").unwrap();
-    let m = re.captures(line).unwrap();
-    m["mask"].to_string()
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramError {
+    line: usize,
+    message: String,
 }
 
-fn parse_rule(line: &str) -> (i64, i64) {
-    let re = Regex::new(r"mem\[(?P<memloc>\d+)\] = (?P<num>\d+)").unwrap();
-    let m = re.captures(line.trim()).unwrap();
-    (
-        m["memloc"].parse::<i64>().unwrap(),
-        m["num"].parse::<i64>().unwrap(),
-    )
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
 }
 
-fn parse_lines(lines: &[String]) -> Vec<Section> {
-    let re = Regex::new(r"mask = .*").unwrap();
-    let mut sections = Vec::new();
-    let mut mask = String::new();
-    let mut rules: Vec<(i64, i64)> = Vec::new();
-    for line in lines {
-        if re.is_match(line) {
-            // Found match line, we have a new rule starting
-            if !mask.is_empty() {
-                sections.push(Section { mask, rules });
+impl std::error::Error for ProgramError {}
+
+/// A docking program for a machine with `width`-bit words.
+#[derive(Debug, Clone, PartialEq)]
+struct MaskProgram {
+    width: u32,
+    instructions: Vec<Instruction>,
+}
+
+impl MaskProgram {
+    fn parse(lines: &[String], width: u32) -> Result<MaskProgram, ProgramError> {
+        if width == 0 || width > 64 {
+            return Err(ProgramError {
+                line: 0,
+                message: format!("Word width {} is not between 1 and 64", width),
+            });
+        }
+        let limit = u64::MAX >> (64 - width);
+        let mut instructions = Vec::new();
+        let mut have_mask = false;
+        for (i, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            let error = |message: String| ProgramError {
+                line: i + 1,
+                message,
+            };
+            let word = |s: &str| match s.parse::<u64>() {
+                Ok(n) if n <= limit => Ok(n),
+                _ => Err(error(format!("{:?} is not a {}-bit number", s, width))),
+            };
+            if line.is_empty() {
+                continue;
+            } else if let Some(mask) = line.strip_prefix("mask = ") {
+                let mask = Mask::parse(mask, width).map_err(error)?;
+                instructions.push(Instruction::SetMask(mask));
+                have_mask = true;
+            } else if let Some(rest) = line.strip_prefix("mem[") {
+                let mut parts = rest.splitn(2, "] = ");
+                let address = parts.next().unwrap();
+                let value = parts.next().ok_or_else(|| {
+                    error(format!("Expected mem[address] = value, got {:?}", line))
+                })?;
+                if !have_mask {
+                    return Err(error("Write before any mask".to_string()));
+                }
+                instructions.push(Instruction::Write {
+                    address: word(address)?,
+                    value: word(value)?,
+                });
+            } else {
+                return Err(error(format!("Unrecognized line {:?}", line)));
             }
-            mask = parse_mask(line);
-            rules = Vec::new();
-        } else {
-            let rule = parse_rule(line);
-            rules.push(rule)
         }
+        Ok(MaskProgram {
+            width,
+            instructions,
+        })
     }
-    if !mask.is_empty() {
-        sections.push(Section { mask, rules });
-    }
-    sections
-}
 
-fn day14a(lines: &[String]) -> i64 {
-    let sections = parse_lines(lines);
-    let mut memory: Vec<i64> = vec![0; 1024 * 1024];
-    for section in sections {
-        let mask = section.mask;
-        for rule in section.rules {
-            let i = apply_mask_to_num(&mask, rule.1);
-            memory[rule.0 as usize] = i;
+    /// Each write with the mask in force when it happens.
+    fn writes(&self) -> Vec<(Mask, u64, u64)> {
+        let mut mask = None;
+        let mut writes = Vec::new();
+        for inst in &self.instructions {
+            match inst {
+                Instruction::SetMask(m) => mask = Some(*m),
+                Instruction::Write { address, value } => {
+                    writes.push((mask.unwrap(), *address, *value));
+                }
+            }
         }
+        writes
     }
-    memory.iter().sum()
-}
 
-fn part_b_addresses(address: i64, mask: &str) -> Vec<i64> {
-    let mut addresses = VecDeque::new();
-    let s = format!("{:036b}", address);
-    let mut startmask: Vec<char> = Vec::new();
-    for i in 0..36 {
-        if mask.chars().nth(i).unwrap() == '1' {
-            startmask.push('1');
-        } else if mask.chars().nth(i).unwrap() == '0' {
-            match s.chars().nth(i) {
-                Some(c) => startmask.push(c),
-                None => startmask.push('0'),
-            }
-        } else if mask.chars().nth(i).unwrap() == 'X' {
-            startmask.push('X');
+    /// Memory sum when the mask applies to values.
+    fn run_a(&self) -> u128 {
+        let mut memory: HashMap<u64, u64> = HashMap::new();
+        for (mask, address, value) in self.writes() {
+            memory.insert(address, mask.apply(value));
         }
+        memory.values().map(|v| *v as u128).sum()
     }
 
-    startmask.reverse();
+    /// Memory sum when the mask applies to addresses.
+    fn run_b(&self) -> u128 {
+        let writes: Vec<(AddressPattern, u64)> = self
+            .writes()
+            .iter()
+            .map(|(mask, address, value)| (mask.addresses(*address), *value))
+            .collect();
+        floating_sum(&writes)
+    }
 
-    addresses.push_back(startmask);
-    while addresses[0].contains(&'X') {
-        let a = addresses.pop_front().unwrap();
-        let mut loc = 0;
-        for (i, c) in a.iter().enumerate() {
-            if c == &'X' {
-                loc = i;
-            }
-        }
-        let mut b = a.clone();
-        b[loc] = '0';
-        addresses.push_back(b);
-        let mut c = a.clone();
-        c[loc] = '1';
-        addresses.push_back(c);
-    }
-
-    addresses
-        .iter()
-        .map(|x| {
-            let x2: String = x.iter().collect();
-            x2
-        })
-        .map(|x: String| i64::from_str_radix(&x, 2).unwrap())
-        .collect()
+    /// A pattern written out the way masks are, one character per bit.
+    fn ternary(&self, pattern: &AddressPattern) -> String {
+        (0..self.width)
+            .rev()
+            .map(|i| {
+                let bit = 1 << i;
+                if pattern.floating & bit != 0 {
+                    'X'
+                } else if pattern.fixed & bit != 0 {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .collect()
+    }
+
+    /// One line per write: the value actually stored for part a, or the
+    /// set of addresses written for part b.
+    fn trace(&self, part: char) -> Vec<String> {
+        self.writes()
+            .iter()
+            .map(|(mask, address, value)| {
+                let effect = match part {
+                    'a' => mask.apply(*value).to_string(),
+                    _ => {
+                        let pattern = mask.addresses(*address);
+                        format!("{} ({} addresses)", self.ternary(&pattern), pattern.len())
+                    }
+                };
+                format!("mem[{}] = {} -> {}", address, value, effect)
+            })
+            .collect()
+    }
+}
+
+fn load_program(lines: &[String]) -> MaskProgram {
+    MaskProgram::parse(lines, 36).unwrap_or_else(|e| panic!("{}", e))
 }
 
 fn day14b(lines: &[String]) -> i64 {
-    let sections = parse_lines(lines);
-    let mut memory: Memtape = Memtape::new();
-    for section in sections {
-        let mask = section.mask;
-        for rule in section.rules {
-            let origaddress = rule.0;
-            let num = rule.1;
-            for address in part_b_addresses(origaddress, &mask) {
-                memory.memory.insert(address, num);
-            }
-        }
-    }
-    memory.sum()
+    load_program(lines).run_b() as i64
+}
+
+/// Every write the program makes, traced under one part's rules.
+pub fn day14_trace(lines: &[String], part: char) -> Result<Vec<String>, ProgramError> {
+    Ok(MaskProgram::parse(lines, 36)?.trace(part))
 }
 
 pub fn day14(lines: &[String], part: char) -> i64 {
@@ -197,6 +291,8 @@ mod tests {
 
     #[test]
     fn test_case() {
+        use day14::{Instruction, Mask, MaskProgram};
+
         let input: Vec<String> = "mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X
         mem[8] = 11
         mem[7] = 101
@@ -205,18 +301,31 @@ mod tests {
             .map(|x| x.trim().to_string())
             .collect();
 
+        let mask = Mask {
+            or: 64,
+            and: (1 << 36) - 1 - 2,
+            floating: (1 << 36) - 1 - 2 - 64,
+        };
         assert_eq!(
-            day14::parse_lines(&input),
-            &[day14::Section {
-                mask: "XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X".to_string(),
-                rules: vec![(8, 11), (7, 101), (8, 0)]
-            }]
+            MaskProgram::parse(&input, 36).unwrap().instructions,
+            vec![
+                Instruction::SetMask(mask),
+                Instruction::Write {
+                    address: 8,
+                    value: 11
+                },
+                Instruction::Write {
+                    address: 7,
+                    value: 101
+                },
+                Instruction::Write {
+                    address: 8,
+                    value: 0
+                },
+            ]
         );
-
-        assert_eq!(day14::make_or_mask(&input[0]), 64);
-        assert_eq!(day14::make_and_mask(&input[0]), 8796093022205);
-        assert_eq!(day14::apply_mask_to_num(&input[0], 11), 73);
-        assert_eq!(day14::apply_mask_to_num(&input[0], 101), 101);
+        assert_eq!(mask.apply(11), 73);
+        assert_eq!(mask.apply(101), 101);
 
         assert_eq!(day14::day14a(&input), 165);
 
@@ -229,4 +338,112 @@ mod tests {
             .collect();
         assert_eq!(day14::day14b(&input2), 208);
     }
+
+    #[test]
+    fn test_address_patterns() {
+        use day14::{AddressPattern, Mask};
+        use proptest::prelude::*;
+        use std::collections::HashMap;
+
+        let pattern = |address: u64, mask: &str| {
+            Mask::parse(mask, mask.len() as u32)
+                .unwrap()
+                .addresses(address)
+        };
+
+        let p = pattern(42, "000000000000000000000000000000X1001X");
+        assert_eq!(p.len(), 4);
+        let contains = |address: u64| {
+            let single = AddressPattern {
+                fixed: address,
+                floating: 0,
+            };
+            p.intersect(&single).is_some()
+        };
+        assert!([26, 27, 58, 59].iter().all(|a| contains(*a)));
+        assert!(!contains(25));
+
+        // 34 floating bits each, overlapping on a quarter of the first.
+        let all_but_two = "X".repeat(34);
+        let writes = vec![
+            (pattern(0, &format!("{}00", all_but_two)), 3),
+            (pattern(0, &format!("00{}", all_but_two)), 5),
+        ];
+        let overlap = 1u128 << 32;
+        let expected = 3 * ((1u128 << 34) - overlap) + 5 * (1u128 << 34);
+        assert_eq!(day14::floating_sum(&writes), expected);
+
+        // Against writing every address a 6-bit mask reaches one at a time.
+        let mask_bit = prop_oneof![Just('0'), Just('1'), Just('X')];
+        let write = (prop::collection::vec(mask_bit, 6), 0u64..64, 1u64..100);
+        proptest!(|(writes in prop::collection::vec(write, 1..8))| {
+            let mut memory: HashMap<u64, u64> = HashMap::new();
+            let mut patterns = Vec::new();
+            for (mask, address, value) in &writes {
+                for a in 0..64u64 {
+                    let reached = mask.iter().enumerate().all(|(i, c)| {
+                        let bit = 1 << (5 - i);
+                        match c {
+                            '0' => a & bit == address & bit,
+                            '1' => a & bit != 0,
+                            _ => true,
+                        }
+                    });
+                    if reached {
+                        memory.insert(a, *value);
+                    }
+                }
+                let mask: String = mask.iter().collect();
+                patterns.push((pattern(*address, &mask), *value));
+            }
+            let expected: u64 = memory.values().sum();
+            prop_assert_eq!(day14::floating_sum(&patterns), expected as u128);
+        });
+    }
+
+    #[test]
+    fn test_mask_program() {
+        use day14::MaskProgram;
+
+        let lines = |s: &str| -> Vec<String> { s.split('\n').map(|x| x.to_string()).collect() };
+        let program = MaskProgram::parse(&lines("mask = XXXX1XX0\nmem[3] = 5\n"), 8).unwrap();
+        assert_eq!(program.run_a(), 12);
+        assert_eq!(program.run_b(), 5 * 64);
+        assert_eq!(program.trace('a'), vec!["mem[3] = 5 -> 12"]);
+        assert_eq!(
+            program.trace('b'),
+            vec!["mem[3] = 5 -> XXXX1XX1 (64 addresses)"]
+        );
+
+        let wide = format!("mask = {}\nmem[0] = {}", "X".repeat(64), u64::MAX);
+        let program = MaskProgram::parse(&lines(&wide), 64).unwrap();
+        assert_eq!(program.run_a(), u64::MAX as u128);
+        assert_eq!(program.run_b(), u64::MAX as u128 * (1 << 64));
+
+        let error = |s: &str, width: u32| {
+            MaskProgram::parse(&lines(s), width)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("", 65),
+            "line 0: Word width 65 is not between 1 and 64"
+        );
+        assert_eq!(error("mask = 1X2", 3), "line 1: Bad mask character '2'");
+        assert_eq!(error("mask = 10", 3), "line 1: Mask is 2 bits, expected 3");
+        assert_eq!(error("mem[4] = 1", 3), "line 1: Write before any mask");
+        assert_eq!(
+            error("mask = XXX\n\nmem[4] = 8", 3),
+            "line 3: \"8\" is not a 3-bit number"
+        );
+        assert_eq!(
+            error("mask = XXX\nmem[x] = 1", 3),
+            "line 2: \"x\" is not a 3-bit number"
+        );
+        assert_eq!(
+            error("mask = XXX\nmem[4] 1", 3),
+            "line 2: Expected mem[address] = value, got \"mem[4] 1\""
+        );
+        assert_eq!(error("nop", 3), "line 1: Unrecognized line \"nop\"");
+    }
 }
@@ -1,38 +1,94 @@
 use std::collections::HashMap;
+use std::mem;
+
+/// The elves' memory game as an endless sequence: the starting numbers,
+/// then on every turn how many turns apart the last number was spoken the
+/// two most recent times (0 if it was new).
+///
+/// Only the last turn each number was spoken is kept, and since nothing
+/// the game says can exceed the number of turns so far, those live in a
+/// flat `Vec<u32>` indexed by number. Starting numbers too large for that
+/// go in a `HashMap` instead. Turns are counted in a `u32`.
+#[derive(Debug, Clone)]
+struct MemoryGame {
+    starting: Vec<u64>,
+    /// Turns played so far, which is also the turn `last` was spoken on.
+    turn: u32,
+    last: Option<u64>,
+    /// Turn each number was last spoken, or 0 if it hasn't been yet.
+    dense: Vec<u32>,
+    sparse: HashMap<u64, u32>,
+}
+
+impl MemoryGame {
+    fn new(starting: &[u64]) -> MemoryGame {
+        MemoryGame {
+            starting: starting.to_vec(),
+            turn: 0,
+            last: None,
+            dense: Vec::new(),
+            sparse: HashMap::new(),
+        }
+    }
+
+    /// Records `number` as spoken on `turn`, returning the turn it was
+    /// spoken before that (or 0).
+    fn remember(&mut self, number: u64, turn: u32) -> u32 {
+        if let Some(slot) = self.dense.get_mut(number as usize) {
+            return mem::replace(slot, turn);
+        }
+        if number <= 2 * turn as u64 + 1024 {
+            self.grow(number as usize + 1);
+            return mem::replace(&mut self.dense[number as usize], turn);
+        }
+        self.sparse.insert(number, turn).unwrap_or(0)
+    }
+
+    /// Makes room in `dense` for numbers below `len`, moving over any that
+    /// had to go in `sparse` until now.
+    fn grow(&mut self, len: usize) {
+        let len = len.next_power_of_two();
+        self.dense.resize(len, 0);
+        let moved: Vec<u64> = self
+            .sparse
+            .keys()
+            .filter(|n| **n < len as u64)
+            .copied()
+            .collect();
+        for n in moved {
+            self.dense[n as usize] = self.sparse.remove(&n).unwrap();
+        }
+    }
+}
+
+impl Iterator for MemoryGame {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let turn = self.turn;
+        let before = match self.last {
+            Some(last) => self.remember(last, turn),
+            None => 0,
+        };
+        let spoken = match self.starting.get(turn as usize) {
+            Some(n) => *n,
+            None if before == 0 => 0,
+            None => (turn - before) as u64,
+        };
+        self.last = Some(spoken);
+        self.turn = turn.checked_add(1)?;
+        Some(spoken)
+    }
+}
 
 pub fn day15(nums: &[i64], part: char) -> i64 {
-    let mut turns: HashMap<i64, Vec<i64>> = HashMap::new();
-    let mut turn = 1;
-    let mut seq: Vec<i64> = Vec::new();
     let partturn = match part {
         'a' => 2020,
         'b' => 30000000,
         _ => panic!(),
     };
-    let maxturn = partturn + 2;
-    for num in nums {
-        turns.entry(*num).or_insert_with(Vec::new).push(turn);
-        seq.push(*num);
-        turn += 1;
-    }
-    loop {
-        let lastnum: i64 = *seq.last().unwrap();
-        let default: Vec<i64> = vec![0];
-        let all_turns = turns.get(&lastnum).unwrap_or(&default);
-        let most_recent_turn = *all_turns.last().unwrap();
-        turns.entry(lastnum).or_insert_with(Vec::new).push(turn - 1);
-        let diff = if most_recent_turn == 0 || most_recent_turn == turn - 1 {
-            0
-        } else {
-            turn - most_recent_turn - 1
-        };
-        seq.push(diff);
-        turn += 1;
-        if turn > maxturn {
-            break;
-        }
-    }
-    *seq.get((partturn as usize) - 1).unwrap()
+    let starting: Vec<u64> = nums.iter().map(|n| *n as u64).collect();
+    MemoryGame::new(&starting).nth(partturn - 1).unwrap() as i64
 }
 
 #[cfg(test)]
@@ -49,6 +105,52 @@ mod tests {
         .collect();
 
         assert_eq!(day15::day15(&input, 'a'), 436);
-        //assert_eq!(day15::day15(&input, 'b'), 175594);
+        assert_eq!(day15::day15(&input, 'b'), 175594);
+    }
+
+    #[test]
+    fn test_memory_game() {
+        use day15::MemoryGame;
+        use proptest::prelude::*;
+
+        let first: Vec<u64> = MemoryGame::new(&[0, 3, 6]).take(10).collect();
+        assert_eq!(first, vec![0, 3, 6, 0, 3, 3, 1, 0, 4, 0]);
+        for (start, turn_2020) in &[
+            ([1, 3, 2], 1),
+            ([2, 1, 3], 10),
+            ([1, 2, 3], 27),
+            ([2, 3, 1], 78),
+            ([3, 2, 1], 438),
+            ([3, 1, 2], 1836),
+        ] {
+            assert_eq!(MemoryGame::new(start).nth(2019), Some(*turn_2020));
+        }
+
+        let naive = |start: &[u64], turns: usize| {
+            let mut spoken = start.to_vec();
+            while spoken.len() < turns {
+                let (last, earlier) = spoken.split_last().unwrap();
+                let next = match earlier.iter().rposition(|n| n == last) {
+                    Some(i) => (earlier.len() - i) as u64,
+                    None => 0,
+                };
+                spoken.push(next);
+            }
+            spoken
+        };
+
+        // 3000 starts out too big for the flat table and moves into it once
+        // the game gets that far; u64::MAX never does.
+        let start = [u64::MAX, 3000, u64::MAX, 7];
+        let mut game = MemoryGame::new(&start);
+        let first: Vec<u64> = game.by_ref().take(5000).collect();
+        assert_eq!(first, naive(&start, 5000));
+        assert_eq!(game.sparse.keys().collect::<Vec<_>>(), vec![&u64::MAX]);
+        assert_eq!(game.dense[3000], 2);
+
+        proptest!(|(start in prop::collection::vec(0u64..2000, 1..6))| {
+            let game: Vec<u64> = MemoryGame::new(&start).take(300).collect();
+            prop_assert_eq!(game, naive(&start, 300));
+        });
     }
 }
@@ -1,8 +1,4 @@
-use std::{
-    cmp::Ordering,
-    collections::HashSet,
-    hash::{Hash, Hasher},
-};
+use std::fmt;
 
 #[derive(Debug, PartialEq, Clone)]
 struct Range {
@@ -10,42 +6,46 @@ struct Range {
     hi: i64,
 }
 
-#[derive(Debug, Clone)]
-struct Rule {
-    name: String,
+/// Inclusive ranges kept sorted and merged, so membership is a binary
+/// search.
+#[derive(Debug, PartialEq, Clone, Default)]
+struct IntervalSet {
     ranges: Vec<Range>,
 }
 
-impl Rule {
-    fn range_size(&self) -> usize {
-        self.ranges.iter().map(|x| (1 + x.hi - x.lo) as usize).sum()
-    }
-}
-
-impl Ord for Rule {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.range_size().cmp(&other.range_size())
+impl IntervalSet {
+    fn new(mut ranges: Vec<Range>) -> IntervalSet {
+        ranges.sort_by_key(|r| r.lo);
+        let mut merged: Vec<Range> = Vec::new();
+        for range in ranges.into_iter().filter(|r| r.lo <= r.hi) {
+            match merged.last_mut() {
+                Some(last) if range.lo <= last.hi.saturating_add(1) => {
+                    last.hi = last.hi.max(range.hi);
+                }
+                _ => merged.push(range),
+            }
+        }
+        IntervalSet { ranges: merged }
     }
-}
 
-impl PartialOrd for Rule {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    fn union(&self, other: &IntervalSet) -> IntervalSet {
+        IntervalSet::new(self.ranges.iter().chain(&other.ranges).cloned().collect())
     }
-}
 
-impl PartialEq for Rule {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+    fn contains(&self, num: i64) -> bool {
+        // The last range starting at or before `num` is the only candidate.
+        let after = match self.ranges.binary_search_by(|r| r.lo.cmp(&num)) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+        after > 0 && num <= self.ranges[after - 1].hi
     }
 }
 
-impl Eq for Rule {}
-
-impl Hash for Rule {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.name.hash(state);
-    }
+#[derive(Debug, PartialEq, Clone)]
+struct Rule {
+    name: String,
+    ranges: IntervalSet,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -56,6 +56,8 @@ struct Ticket {
 #[derive(Debug, PartialEq)]
 struct Day {
     rules: Vec<Rule>,
+    /// Every value at least one rule allows.
+    any_rule: IntervalSet,
     my_ticket: Ticket,
     nearby_tickets: Vec<Ticket>,
 }
@@ -80,7 +82,7 @@ fn parse_rules(s: &str) -> Vec<Rule> {
         }
         rulesvec.push(Rule {
             name,
-            ranges: rangelist,
+            ranges: IntervalSet::new(rangelist),
         });
     }
     rulesvec
@@ -107,98 +109,269 @@ fn parse_input(s: &str) -> Day {
         let nearby_ticket = parse_ticket(line);
         nearby_tickets.push(nearby_ticket);
     }
+    let any_rule = rules
+        .iter()
+        .fold(IntervalSet::default(), |set, r| set.union(&r.ranges));
     Day {
         rules,
+        any_rule,
         my_ticket,
         nearby_tickets,
     }
 }
 
 fn check_num(day: &Day, num: i64) -> bool {
-    let mut valids = Vec::new();
-    for rule in &day.rules {
-        let mut in_range = Vec::new();
-        for range in &rule.ranges {
-            in_range.push(num >= range.lo && num <= range.hi);
+    day.any_rule.contains(num)
+}
+
+/// One nearby ticket: the values no rule allows, and for every other
+/// value the rules it satisfies.
+#[derive(Debug, PartialEq)]
+struct TicketScan {
+    nums: Vec<i64>,
+    invalid: Vec<i64>,
+    matches: Vec<(i64, Vec<String>)>,
+}
+
+/// How much of the nearby tickets a rule accounts for: the values it
+/// allows out of all values scanned, and how many of those no other rule
+/// allows.
+#[derive(Debug, PartialEq)]
+struct RuleCoverage {
+    name: String,
+    matched: usize,
+    total: usize,
+    only: usize,
+}
+
+#[derive(Debug, PartialEq)]
+struct ScanReport {
+    tickets: Vec<TicketScan>,
+    coverage: Vec<RuleCoverage>,
+}
+
+impl ScanReport {
+    fn new(day: &Day) -> ScanReport {
+        let mut coverage: Vec<RuleCoverage> = day
+            .rules
+            .iter()
+            .map(|r| RuleCoverage {
+                name: r.name.clone(),
+                matched: 0,
+                total: 0,
+                only: 0,
+            })
+            .collect();
+        let mut tickets = Vec::new();
+        for ticket in &day.nearby_tickets {
+            let mut scan = TicketScan {
+                nums: ticket.nums.clone(),
+                invalid: Vec::new(),
+                matches: Vec::new(),
+            };
+            for num in &ticket.nums {
+                let fits: Vec<usize> = (0..day.rules.len())
+                    .filter(|r| day.rules[*r].ranges.contains(*num))
+                    .collect();
+                for rule in &mut coverage {
+                    rule.total += 1;
+                }
+                for r in &fits {
+                    coverage[*r].matched += 1;
+                    if fits.len() == 1 {
+                        coverage[*r].only += 1;
+                    }
+                }
+                if fits.is_empty() {
+                    scan.invalid.push(*num);
+                } else {
+                    let names = fits.iter().map(|r| day.rules[*r].name.clone()).collect();
+                    scan.matches.push((*num, names));
+                }
+            }
+            tickets.push(scan);
         }
-        let valid_num = in_range.iter().any(|b| *b);
-        valids.push(valid_num);
+        ScanReport { tickets, coverage }
+    }
+
+    /// Sum of every value no rule allows.
+    fn error_rate(&self) -> i64 {
+        self.tickets.iter().flat_map(|t| &t.invalid).sum()
     }
-    valids.iter().any(|b| *b)
 }
 
-fn day16a(input: &str) -> i64 {
-    let day = parse_input(input);
-    let mut invalid_nums = Vec::new();
-    for other_ticket in &day.nearby_tickets {
-        for num in &other_ticket.nums {
-            if !check_num(&day, *num) {
-                invalid_nums.push(num);
+impl fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, ticket) in self.tickets.iter().enumerate() {
+            let nums: Vec<String> = ticket.nums.iter().map(|n| n.to_string()).collect();
+            write!(f, "ticket {} ({}):", i, nums.join(","))?;
+            if ticket.invalid.is_empty() {
+                writeln!(f, " valid")?;
+            } else {
+                let invalid: Vec<String> = ticket.invalid.iter().map(|n| n.to_string()).collect();
+                writeln!(f, " no rule allows {}", invalid.join(", "))?;
             }
+            for (num, names) in &ticket.matches {
+                writeln!(f, "  {}: {}", num, names.join(", "))?;
+            }
+        }
+        for rule in &self.coverage {
+            writeln!(
+                f,
+                "{}: {}/{} values, {} only by this rule",
+                rule.name, rule.matched, rule.total, rule.only
+            )?;
         }
+        Ok(())
     }
-    invalid_nums.iter().copied().sum()
+}
+
+fn day16a(input: &str) -> i64 {
+    ScanReport::new(&parse_input(input)).error_rate()
 }
 
 fn is_valid(rule: &Rule, nums: &[i64]) -> bool {
-    for num in nums {
-        let mut valid = false;
-        for range in &rule.ranges {
-            if num >= &range.lo && num <= &range.hi {
-                valid = true;
-                break;
+    nums.iter().all(|num| rule.ranges.contains(*num))
+}
+
+pub fn day16_report(input: &str) -> String {
+    ScanReport::new(&parse_input(input)).to_string()
+}
+
+/// Hopcroft-Karp maximum matching between columns and rules, where
+/// `candidates[c]` lists the rules column `c` could be. Returns the rule
+/// matched to each column, if any.
+fn match_columns(candidates: &[Vec<usize>], rules: usize) -> Vec<Option<usize>> {
+    let mut col_rule: Vec<Option<usize>> = vec![None; candidates.len()];
+    let mut rule_col: Vec<Option<usize>> = vec![None; rules];
+    loop {
+        // Layer the columns by alternating path length from a free one.
+        let mut dist: Vec<Option<usize>> = col_rule
+            .iter()
+            .map(|r| if r.is_none() { Some(0) } else { None })
+            .collect();
+        let mut queue: Vec<usize> = (0..candidates.len())
+            .filter(|c| col_rule[*c].is_none())
+            .collect();
+        let mut found = false;
+        let mut i = 0;
+        while i < queue.len() {
+            let c = queue[i];
+            i += 1;
+            for r in &candidates[c] {
+                match rule_col[*r] {
+                    None => found = true,
+                    Some(next) if dist[next].is_none() => {
+                        dist[next] = dist[c].map(|d| d + 1);
+                        queue.push(next);
+                    }
+                    Some(_) => {}
+                }
             }
         }
-        if !valid {
-            return false;
+        if !found {
+            return col_rule;
+        }
+        for c in 0..candidates.len() {
+            if col_rule[c].is_none() {
+                augment(c, candidates, &mut dist, &mut col_rule, &mut rule_col);
+            }
         }
     }
-    true
 }
 
-fn step(rules: HashSet<Rule>, numlists: Vec<Vec<i64>>) -> Option<Vec<String>> {
-    let mut sortedrules: Vec<Rule> = rules.iter().cloned().collect();
-    sortedrules.sort();
-    sortedrules.reverse();
-    for rule in &sortedrules {
-        if rules.len() == 20 {
-            println!("Rule {:?}", rule.name);
-        }
-        if rules.len() == 19 {
-            println!("  Rule {:?}", rule.name);
-        }
-        if rules.len() == 18 {
-            println!("    Rule {:?}", rule.name);
+/// Looks for a shortest augmenting path from column `c` along the layers
+/// in `dist`, flipping it into the matching if there is one.
+fn augment(
+    c: usize,
+    candidates: &[Vec<usize>],
+    dist: &mut Vec<Option<usize>>,
+    col_rule: &mut Vec<Option<usize>>,
+    rule_col: &mut Vec<Option<usize>>,
+) -> bool {
+    for r in &candidates[c] {
+        let free = match rule_col[*r] {
+            None => true,
+            Some(next) => {
+                dist[next].is_some()
+                    && dist[next] == dist[c].map(|d| d + 1)
+                    && augment(next, candidates, dist, col_rule, rule_col)
+            }
+        };
+        if free {
+            col_rule[c] = Some(*r);
+            rule_col[*r] = Some(c);
+            return true;
         }
-        if rules.len() == 17 {
-            println!("      Rule {:?}", rule.name);
+    }
+    dist[c] = None;
+    false
+}
+
+/// Whether column `c` could take rule `r` instead of the one it has in
+/// the complete matching `col_rule`. That's the case when the column
+/// holding `r` can move along, column to column, until one of them takes
+/// over the rule `c` gives up.
+fn can_swap(c: usize, r: usize, candidates: &[Vec<usize>], col_rule: &[Option<usize>]) -> bool {
+    let target = col_rule[c];
+    let holder = |rule: usize| col_rule.iter().position(|m| *m == Some(rule));
+    let mut seen = vec![false; candidates.len()];
+    let mut stack: Vec<Option<usize>> = vec![holder(r)];
+    while let Some(col) = stack.pop() {
+        let col = match col {
+            // Nobody holds this rule, so the chain can end on it.
+            None => return true,
+            Some(col) => col,
+        };
+        if col == c || seen[col] {
+            continue;
         }
-        let nums = &numlists[0];
-        if is_valid(rule, &nums) {
-            // this rule is valid, remove it from the list, remove this column of nums, try next rule
-            let mut newrules = rules.clone();
-            newrules.remove(rule);
-            if newrules.is_empty() {
-                return Some(vec![rule.name.clone()]);
+        seen[col] = true;
+        for next in &candidates[col] {
+            if Some(*next) == target {
+                return true;
             }
-            let result = step(newrules, numlists[1..].to_vec());
-            if let Some(r) = result {
-                let mut newvec: Vec<String> = vec![rule.name.clone()];
-                let mut prevvec = r;
-                newvec.append(&mut prevvec);
-                return Some(newvec);
+            if Some(*next) != col_rule[col] {
+                stack.push(holder(*next));
             }
         }
     }
-    // Uh oh, we made it this far. No solution. return None
-    None
+    false
 }
 
 fn is_valid_ticket(day: &Day, ticket: &Ticket) -> bool {
     ticket.nums.iter().all(|x| check_num(day, *x))
 }
 
-fn order_of_answers(input: &str) -> Vec<String> {
+/// How the ticket columns map onto the rules. When there isn't exactly
+/// one way, each column lists the fields it could be: every rule its
+/// values fit if nothing works, or the fields it takes in some complete
+/// assignment if several do.
+#[derive(Debug, PartialEq)]
+enum FieldAssignment {
+    Unique(Vec<String>),
+    Ambiguous(Vec<Vec<String>>),
+    Impossible(Vec<Vec<String>>),
+}
+
+impl fmt::Display for FieldAssignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (heading, columns) = match self {
+            FieldAssignment::Unique(names) => {
+                return write!(f, "Fields in order: {}", names.join(", "));
+            }
+            FieldAssignment::Ambiguous(columns) => ("More than one field order fits", columns),
+            FieldAssignment::Impossible(columns) => ("No field order fits", columns),
+        };
+        write!(f, "{}:", heading)?;
+        for (i, names) in columns.iter().enumerate() {
+            write!(f, "\n  column {}: {}", i, names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn assign_fields(input: &str) -> FieldAssignment {
     let mut day = parse_input(input);
     day.nearby_tickets = day
         .nearby_tickets
@@ -206,25 +379,54 @@ fn order_of_answers(input: &str) -> Vec<String> {
         .filter(|x| is_valid_ticket(&day, x))
         .cloned()
         .collect();
-    let mut numlists: Vec<Vec<i64>> = Vec::new();
+    let mut candidates: Vec<Vec<usize>> = Vec::new();
     for i in 0..day.my_ticket.nums.len() {
         let numlist: Vec<i64> = day
             .nearby_tickets
             .iter()
             .map(|x| *x.nums.get(i).unwrap())
             .collect();
-        numlists.push(numlist);
+        let fits = (0..day.rules.len())
+            .filter(|r| is_valid(&day.rules[*r], &numlist))
+            .collect();
+        candidates.push(fits);
     }
-    let mut newrules = HashSet::new();
-    for rule in &day.rules {
-        newrules.insert(rule.clone());
+    let names = |rules: &[usize]| -> Vec<String> {
+        rules.iter().map(|r| day.rules[*r].name.clone()).collect()
+    };
+
+    let col_rule = match_columns(&candidates, day.rules.len());
+    if col_rule.iter().any(|r| r.is_none()) {
+        return FieldAssignment::Impossible(candidates.iter().map(|c| names(c)).collect());
+    }
+    let possible: Vec<Vec<usize>> = candidates
+        .iter()
+        .enumerate()
+        .map(|(c, rules)| {
+            rules
+                .iter()
+                .copied()
+                .filter(|r| col_rule[c] == Some(*r) || can_swap(c, *r, &candidates, &col_rule))
+                .collect()
+        })
+        .collect();
+    if possible.iter().all(|rules| rules.len() == 1) {
+        FieldAssignment::Unique(
+            col_rule
+                .iter()
+                .map(|r| names(&[r.unwrap()]).remove(0))
+                .collect(),
+        )
+    } else {
+        FieldAssignment::Ambiguous(possible.iter().map(|c| names(c)).collect())
     }
-    let rootstep = step(newrules, numlists);
-    rootstep.unwrap()
 }
 
 fn day16b(input: &str) -> i64 {
-    let answers = order_of_answers(input);
+    let answers = match assign_fields(input) {
+        FieldAssignment::Unique(answers) => answers,
+        other => panic!("{}", other),
+    };
     let day = parse_input(input);
     let combos: Vec<(i64, String)> = day
         .my_ticket
@@ -283,4 +485,122 @@ nearby tickets:
         //day16::day16b(&input);
         day16::day16b(&input2);
     }
+
+    #[test]
+    fn test_assign_fields() {
+        use day16::FieldAssignment;
+
+        let input = |rules: &str, nearby: &str| {
+            format!(
+                "{}\n\nyour ticket:\n1,2,3\n\nnearby tickets:\n{}",
+                rules, nearby
+            )
+        };
+        let names = |columns: &[&[&str]]| -> Vec<Vec<String>> {
+            columns
+                .iter()
+                .map(|c| c.iter().map(|s| s.to_string()).collect())
+                .collect()
+        };
+
+        let rules = "class: 0-1 or 4-19\nrow: 0-5 or 8-19\nseat: 0-13 or 16-19";
+        assert_eq!(
+            day16::assign_fields(&input(rules, "3,9,18\n15,1,5\n5,14,9")),
+            FieldAssignment::Unique(vec!["row".into(), "class".into(), "seat".into()])
+        );
+
+        // Column 0 is pinned, but row and seat could go either way round.
+        let rules = "class: 0-1 or 10-10\nrow: 0-9 or 20-29\nseat: 0-9 or 30-39";
+        let ambiguous = day16::assign_fields(&input(rules, "10,5,6\n1,2,3"));
+        assert_eq!(
+            ambiguous,
+            FieldAssignment::Ambiguous(names(&[&["class"], &["row", "seat"], &["row", "seat"]]))
+        );
+        assert_eq!(
+            ambiguous.to_string(),
+            "More than one field order fits:
+  column 0: class
+  column 1: row, seat
+  column 2: row, seat"
+        );
+
+        // Columns 1 and 2 both only fit row.
+        assert_eq!(
+            day16::assign_fields(&input(rules, "10,25,26\n1,2,3")),
+            FieldAssignment::Impossible(names(&[&["class"], &["row"], &["row"]]))
+        );
+
+        // Greedily giving column 0 the first rule it fits leaves nothing
+        // for column 1, so the matching has to reshuffle.
+        let rules = "a: 0-9\nb: 0-9 or 20-29\nc: 0-9 or 30-39";
+        assert_eq!(
+            day16::assign_fields(&input(rules, "5,1,35\n1,1,1")),
+            FieldAssignment::Ambiguous(names(&[&["a", "b"], &["a", "b"], &["c"]]))
+        );
+        assert_eq!(
+            day16::assign_fields(&input(rules, "25,1,35\n1,1,1")),
+            FieldAssignment::Unique(vec!["b".into(), "a".into(), "c".into()])
+        );
+    }
+
+    #[test]
+    fn test_scan_report() {
+        use day16::{IntervalSet, Range};
+
+        let set = IntervalSet::new(vec![
+            Range { lo: 5, hi: 7 },
+            Range { lo: 1, hi: 3 },
+            Range { lo: 4, hi: 4 },
+            Range { lo: 10, hi: 12 },
+            Range { lo: 11, hi: 11 },
+            Range { lo: 9, hi: 8 },
+        ]);
+        assert_eq!(
+            set.ranges,
+            vec![Range { lo: 1, hi: 7 }, Range { lo: 10, hi: 12 }]
+        );
+        let inside: Vec<i64> = (0..14).filter(|n| set.contains(*n)).collect();
+        assert_eq!(inside, vec![1, 2, 3, 4, 5, 6, 7, 10, 11, 12]);
+        let top = IntervalSet::new(vec![
+            Range {
+                lo: i64::MAX,
+                hi: i64::MAX,
+            },
+            Range { lo: 0, hi: 0 },
+        ]);
+        assert!(top.contains(i64::MAX) && !top.contains(i64::MAX - 1));
+
+        let input = "class: 1-3 or 5-7
+row: 6-11 or 33-44
+seat: 13-40 or 45-50
+
+your ticket:
+7,1,14
+
+nearby tickets:
+7,3,47
+40,4,50
+55,2,20
+38,6,12";
+        assert_eq!(
+            day16::day16_report(input),
+            "ticket 0 (7,3,47): valid
+  7: class, row
+  3: class
+  47: seat
+ticket 1 (40,4,50): no rule allows 4
+  40: row, seat
+  50: seat
+ticket 2 (55,2,20): no rule allows 55
+  2: class
+  20: seat
+ticket 3 (38,6,12): no rule allows 12
+  38: row, seat
+  6: class, row
+class: 4/12 values, 2 only by this rule
+row: 4/12 values, 0 only by this rule
+seat: 5/12 values, 3 only by this rule
+"
+        );
+    }
 }
@@ -1,63 +1,48 @@
-use crate::{infinite_field_3d::InfiniteField3d, infinite_field_4d::InfiniteField4d};
-
-fn next_step_a(field: &InfiniteField3d<i64>) -> InfiniteField3d<i64> {
-    let mut newfield: InfiniteField3d<i64> = InfiniteField3d::new();
-    for x in field.xlims.0 - 1..=field.xlims.1 + 1 {
-        for y in field.ylims.0 - 1..=field.ylims.1 + 1 {
-            for z in field.zlims.0 - 1..=field.zlims.1 + 1 {
-                let num_neighbors = field.num_neighbors(x as isize, y as isize, z as isize);
-                if num_neighbors == 3 {
-                    newfield.set(x as isize, y as isize, z as isize, 1);
-                } else if !(2..=3).contains(&num_neighbors) {
-                    if field.get(x as isize, y as isize, z as isize) == 1 {
-                        newfield.set(x as isize, y as isize, z as isize, 0);
-                    }
-                } else {
-                    newfield.set(
-                        x as isize,
-                        y as isize,
-                        z as isize,
-                        field.get(x as isize, y as isize, z as isize),
-                    );
-                }
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+use crate::frames::{Frame, FrameRecorder};
+use crate::history::History;
+use crate::infinite_field::{neighborhood, Field, InfiniteField};
+use crate::sparse_field::SparseField;
+
+/// Positions of the active cubes.
+type Cubes<const D: usize> = BTreeSet<[isize; D]>;
+
+/// One boot cycle, with neighbour counts kept in a `C`. Rather than asking
+/// every cell in range how many active neighbours it has, each active cube
+/// adds itself to the counts of the cells around it, so only cells next to
+/// something are ever looked at.
+fn step<C: Field<u16, D>, const D: usize>(cubes: &Cubes<D>) -> Cubes<D> {
+    let offsets = neighborhood::<D>();
+    let mut counts = C::new();
+    let mut touched = Vec::new();
+    for cube in cubes {
+        for offset in &offsets {
+            let mut pos = *cube;
+            for k in 0..D {
+                pos[k] += offset[k];
             }
-        }
-    }
-    newfield
-}
-
-fn next_step_b(field: &InfiniteField4d<i64>) -> InfiniteField4d<i64> {
-    let mut newfield: InfiniteField4d<i64> = InfiniteField4d::new();
-    for x in field.xlims.0 - 1..=field.xlims.1 + 1 {
-        for y in field.ylims.0 - 1..=field.ylims.1 + 1 {
-            for z in field.zlims.0 - 1..=field.zlims.1 + 1 {
-                for w in field.wlims.0 - 1..=field.wlims.1 + 1 {
-                    let num_neighbors =
-                        field.num_neighbors(x as isize, y as isize, z as isize, w as isize);
-                    if num_neighbors == 3 {
-                        newfield.set(x as isize, y as isize, z as isize, w as isize, 1);
-                    } else if !(2..=3).contains(&num_neighbors) {
-                        if field.get(x as isize, y as isize, z as isize, w as isize) == 1 {
-                            newfield.set(x as isize, y as isize, z as isize, w as isize, 0);
-                        }
-                    } else {
-                        newfield.set(
-                            x as isize,
-                            y as isize,
-                            z as isize,
-                            w as isize,
-                            field.get(x as isize, y as isize, z as isize, w as isize),
-                        );
-                    }
-                }
+            let count = counts.get(pos);
+            if count == 0 {
+                touched.push(pos);
             }
+            counts.set(pos, count + 1);
         }
     }
-    newfield
+    touched
+        .into_iter()
+        .filter(|pos| {
+            let count = counts.get(*pos);
+            count == 3 || (count == 2 && cubes.contains(pos))
+        })
+        .collect()
 }
 
-fn parse_input_a(input: &str) -> InfiniteField3d<i64> {
-    let mut field = InfiniteField3d::new();
+/// The initial slice, at zero in every dimension past the first two. Needs
+/// at least two dimensions to hold the rows.
+fn parse_input<const D: usize>(input: &str) -> InfiniteField<i64, D> {
+    let mut field = InfiniteField::new();
     for (lnum, line) in input.split('\n').enumerate() {
         for (cnum, c) in line.trim().chars().enumerate() {
             let vnum = match c {
@@ -65,41 +50,153 @@ fn parse_input_a(input: &str) -> InfiniteField3d<i64> {
                 '.' => 0,
                 _ => panic!(),
             };
-            field.set(cnum as isize, lnum as isize, 0, vnum);
+            let mut pos = [0; D];
+            pos[0] = cnum as isize;
+            pos[1] = lnum as isize;
+            field.set(pos, vnum);
         }
     }
     field
 }
 
-fn parse_input_b(input: &str) -> InfiniteField4d<i64> {
-    let mut field = InfiniteField4d::new();
-    for (lnum, line) in input.split('\n').enumerate() {
-        for (cnum, c) in line.trim().chars().enumerate() {
-            let vnum = match c {
-                '#' => 1,
-                '.' => 0,
-                _ => panic!(),
-            };
-            field.set(cnum as isize, lnum as isize, 0, 0, vnum);
-        }
+fn active<F: Field<i64, D>, const D: usize>(field: &F) -> Cubes<D> {
+    field.cells().into_iter().map(|(pos, _)| pos).collect()
+}
+
+fn field_from<F: Field<i64, D>, const D: usize>(cubes: &Cubes<D>) -> F {
+    let mut field = F::new();
+    for pos in cubes {
+        field.set(*pos, 1);
     }
     field
 }
 
+/// Active cubes after six boot cycles in `D` dimensions, counting
+/// neighbours in a `C`.
+fn boot_with<C: Field<u16, D>, const D: usize>(input: &str) -> i64 {
+    let initial = active(&parse_input::<D>(input));
+    let history = History::run(initial, 6, step::<C, D>);
+    history.generation(6).unwrap().len() as i64
+}
+
+fn boot<const D: usize>(input: &str) -> i64 {
+    boot_with::<InfiniteField<u16, D>, D>(input)
+}
+
 fn day17a(input: &str) -> i64 {
-    let mut field = parse_input_a(&input);
-    for _ in 0..6 {
-        field = next_step_a(&field);
-    }
-    field.sum()
+    boot::<3>(input)
 }
 
 fn day17b(input: &str) -> i64 {
-    let mut field = parse_input_b(&input);
+    boot::<4>(input)
+}
+
+/// Boots once to warm up, then `runs` more times, keeping the fastest.
+fn best_of(input: &str, runs: usize, boot: fn(&str) -> i64) -> (i64, Duration) {
+    let active = boot(input);
+    let mut best = Duration::MAX;
+    for _ in 0..runs.max(1) {
+        let start = Instant::now();
+        let again = boot(input);
+        best = best.min(start.elapsed());
+        assert_eq!(again, active);
+    }
+    (active, best)
+}
+
+/// Times booting in `D` dimensions with each field backend.
+fn bench<const D: usize>(input: &str, runs: usize) -> String {
+    let (dense, dense_time) = best_of(input, runs, boot_with::<InfiniteField<u16, D>, D>);
+    let (sparse, sparse_time) = best_of(input, runs, boot_with::<SparseField<u16, D>, D>);
+    format!(
+        "{}D: dense {} in {:?}, sparse {} in {:?} (best of {})",
+        D,
+        dense,
+        dense_time,
+        sparse,
+        sparse_time,
+        runs.max(1)
+    )
+}
+
+/// Compares the dense and sparse field backends booting in 3 to 6
+/// dimensions, one line each, taking the best of `runs` timings.
+pub fn day17_bench(input: &str, runs: usize) -> Vec<String> {
+    vec![
+        bench::<3>(input, runs),
+        bench::<4>(input, runs),
+        bench::<5>(input, runs),
+        bench::<6>(input, runs),
+    ]
+}
+
+/// Boots the cubes in any number of dimensions from 2 to 6.
+pub fn day17_dimensions(input: &str, dimensions: usize) -> Option<i64> {
+    match dimensions {
+        2 => Some(boot::<2>(input)),
+        3 => Some(boot::<3>(input)),
+        4 => Some(boot::<4>(input)),
+        5 => Some(boot::<5>(input)),
+        6 => Some(boot::<6>(input)),
+        _ => None,
+    }
+}
+
+/// Draws every (z, w) slice of a field in one frame, z layers left to right
+/// and w layers top to bottom, with a blank line of cells between them.
+fn draw_slices<F: Fn(i64, i64, i64, i64) -> bool>(lims: [(i64, i64); 4], active: F) -> Frame {
+    let size = |l: (i64, i64)| (l.1 - l.0 + 1) as usize;
+    let [xlims, ylims, zlims, wlims] = lims;
+    let (nx, ny, nz, nw) = (size(xlims), size(ylims), size(zlims), size(wlims));
+    let mut frame = Frame::new(nz * (nx + 1) - 1, nw * (ny + 1) - 1, ' ');
+    for (wi, w) in (wlims.0..=wlims.1).enumerate() {
+        for (zi, z) in (zlims.0..=zlims.1).enumerate() {
+            for (yi, y) in (ylims.0..=ylims.1).enumerate() {
+                for (xi, x) in (xlims.0..=xlims.1).enumerate() {
+                    let c = if active(x, y, z, w) { '#' } else { '.' };
+                    frame.set(zi * (nx + 1) + xi, wi * (ny + 1) + yi, c);
+                }
+            }
+        }
+    }
+    frame
+}
+
+/// Draws the field's (z, w) slices, or just the slices at zero in any
+/// dimensions past the fourth.
+fn frame<const D: usize>(field: &InfiniteField<i64, D>) -> Frame {
+    let mut lims = [(0, 0); 4];
+    for (k, lim) in field.lims().iter().take(4).enumerate() {
+        lims[k] = *lim;
+    }
+    draw_slices(lims, |x, y, z, w| {
+        let mut pos = [0; D];
+        for (k, c) in [x, y, z, w].iter().take(D).enumerate() {
+            pos[k] = *c as isize;
+        }
+        field.get(pos) != 0
+    })
+}
+
+fn record_boot<const D: usize>(input: &str, recorder: &mut FrameRecorder) {
+    let field = parse_input::<D>(input);
+    recorder.record(frame(&field));
+    let mut cubes = active(&field);
     for _ in 0..6 {
-        field = next_step_b(&field);
+        cubes = step::<InfiniteField<u16, D>, D>(&cubes);
+        recorder.record(frame(&field_from(&cubes)));
     }
-    field.sum()
+}
+
+/// The six boot cycles of a part, starting from the initial slice.
+pub fn day17_frames(input: &str, part: char) -> FrameRecorder {
+    let mut recorder = FrameRecorder::new();
+    match part {
+        'a' => record_boot::<3>(input, &mut recorder),
+        'b' => record_boot::<4>(input, &mut recorder),
+        _ => {}
+    }
+    recorder
 }
 
 pub fn day17(input: &str, part: char) -> i64 {
@@ -113,6 +210,8 @@ pub fn day17(input: &str, part: char) -> i64 {
 #[cfg(test)]
 mod tests {
     use crate::day17;
+    use crate::infinite_field::InfiniteField;
+    use crate::sparse_field::SparseField;
 
     #[test]
     fn test_case() {
@@ -120,22 +219,46 @@ mod tests {
         ..#
         ###"
         .to_string();
-        let mut field = day17::parse_input_a(&input);
-        assert_eq!(field.sum(), 5);
+        let mut cubes = day17::active(&day17::parse_input::<3>(&input));
+        assert_eq!(cubes.len(), 5);
         for i in 0..6 {
-            field = day17::next_step_a(&field);
-            println!("Iteration {:?}: {:?}", i, field.sum());
+            cubes = day17::step::<InfiniteField<u16, 3>, 3>(&cubes);
+            println!("Iteration {:?}: {:?}", i, cubes.len());
         }
-        println!("{}", field);
-        assert_eq!(field.sum(), 112);
+        println!("{}", day17::field_from::<InfiniteField<i64, 3>, 3>(&cubes));
+        assert_eq!(cubes.len(), 112);
 
-        let mut field = day17::parse_input_b(&input);
-        assert_eq!(field.sum(), 5);
+        let mut cubes = day17::active(&day17::parse_input::<4>(&input));
+        assert_eq!(cubes.len(), 5);
         for i in 0..6 {
-            field = day17::next_step_b(&field);
-            println!("Iteration {:?}: {:?}", i, field.sum());
+            cubes = day17::step::<InfiniteField<u16, 4>, 4>(&cubes);
+            println!("Iteration {:?}: {:?}", i, cubes.len());
         }
-        //println!("{}", field);
-        assert_eq!(field.sum(), 848);
+        assert_eq!(cubes.len(), 848);
+
+        let recorder = day17::day17_frames(&input, 'a');
+        assert_eq!(recorder.frames().len(), 7);
+        assert_eq!(recorder.frames()[0].to_string(), ".#.\n..#\n###\n");
+        assert_eq!(day17::day17_frames(&input, 'b').frames().len(), 7);
+
+        assert_eq!(day17::day17a(&input), 112);
+        assert_eq!(day17::day17b(&input), 848);
+        // A lone cube dies straight away, and nothing ever comes back.
+        assert_eq!(day17::day17a("#"), 0);
+        // A glider in the plane is back to five cells, and the extra
+        // dimensions match a brute-force count.
+        assert_eq!(day17::day17_dimensions(&input, 2), Some(5));
+        assert_eq!(day17::day17_dimensions(&input, 5), Some(5760));
+        assert_eq!(day17::day17_dimensions(&input, 1), None);
+        assert_eq!(day17::day17_dimensions(&input, 7), None);
+
+        // Counting neighbours in a sparse field gives the same cubes.
+        assert_eq!(day17::boot_with::<SparseField<u16, 3>, 3>(&input), 112);
+        assert_eq!(day17::boot_with::<SparseField<u16, 4>, 4>(&input), 848);
+
+        let (active, _) = day17::best_of(&input, 2, day17::day17a);
+        assert_eq!(active, 112);
+        assert!(day17::bench::<3>(&input, 2).starts_with("3D: dense 112 in "));
+        assert!(day17::bench::<3>(&input, 0).contains(", sparse 112 in "));
     }
 }
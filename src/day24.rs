@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use nom::{branch::alt, bytes::complete::tag, combinator::map_res, multi::many0, IResult};
 
+use crate::frames::{Frame, FrameRecorder};
+
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 struct HexCoord {
     x: i64,
@@ -167,6 +169,66 @@ pub fn day24b(s: &[String]) -> i64 {
     black_tiles(&next).len() as i64
 }
 
+/// Draws each day's floor on a shared canvas covering every black tile of
+/// every day. Tiles sit on alternate cells of a doubled-width grid, so each
+/// row is offset by half a tile from the one above, with north at the top.
+fn draw_days(days: &[HashMap<HexCoord, usize>]) -> FrameRecorder {
+    let cell = |t: &HexCoord| (2 * t.x + t.y, -t.y);
+    let cells: Vec<(i64, i64)> = days
+        .iter()
+        .flat_map(black_tiles)
+        .map(|t| cell(&t))
+        .collect();
+    let mut recorder = FrameRecorder::new();
+    if cells.is_empty() {
+        return recorder;
+    }
+    let col0 = cells.iter().map(|c| c.0).min().unwrap() - 2;
+    let col1 = cells.iter().map(|c| c.0).max().unwrap() + 2;
+    let row0 = cells.iter().map(|c| c.1).min().unwrap() - 1;
+    let row1 = cells.iter().map(|c| c.1).max().unwrap() + 1;
+    for hexmap in days {
+        let width = (col1 - col0 + 1) as usize;
+        let height = (row1 - row0 + 1) as usize;
+        let mut frame = Frame::new(width, height, ' ');
+        for row in row0..=row1 {
+            for col in col0..=col1 {
+                if (col + row) % 2 != 0 {
+                    continue;
+                }
+                let tile = HexCoord {
+                    x: (col + row) / 2,
+                    y: -row,
+                };
+                let black = hexmap.get(&tile).unwrap_or(&0) % 2 != 0;
+                let c = if black { '#' } else { '.' };
+                frame.set((col - col0) as usize, (row - row0) as usize, c);
+            }
+        }
+        recorder.record(frame);
+    }
+    recorder
+}
+
+/// The floor as laid out from the instructions, then after each of
+/// `days` days.
+pub fn day24_frames(s: &[String], days: usize) -> FrameRecorder {
+    let mut hexmap: HashMap<HexCoord, usize> = HashMap::new();
+    for line in s {
+        let coords = HexCoord::from_str(line);
+        let sum = sum_coords(coords);
+        let count = hexmap.entry(sum).or_insert(0);
+        *count += 1;
+    }
+
+    let mut history = vec![hexmap];
+    for _ in 0..days {
+        let next = step(history[history.len() - 1].clone());
+        history.push(next);
+    }
+    draw_days(&history)
+}
+
 impl HexCoord {
     pub fn from_str(s: &str) -> Vec<HexCoord> {
         let coords = hex_dirs(s);
@@ -219,4 +281,22 @@ mod tests {
         let next = day24::step(next);
         assert_eq!(day24::black_tiles(&next).len(), 14);
     }
+
+    #[test]
+    fn test_frames() {
+        let lines = vec!["e".to_string(), "nw".to_string(), "nwe".to_string()];
+        let recorder = day24::day24_frames(&lines, 1);
+        assert_eq!(recorder.frames().len(), 2);
+        // e, nw and ne of the reference tile, with north at the top.
+        assert_eq!(
+            recorder.frames()[0].to_string(),
+            concat!(
+                ". . . . .\n",
+                " . . . . \n",
+                ". # # . .\n",
+                " . . # . \n",
+                ". . . . .\n",
+            )
+        );
+    }
 }
@@ -0,0 +1,247 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+/// One generation of an automaton drawn as a 2D grid of characters, the
+/// same way the puzzles draw them ('#' for live cells, '.' for dead ones).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<char>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize, fill: char) -> Frame {
+        Frame {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+
+    /// Reads a frame from rendered text, padding short lines with spaces.
+    pub fn from_text(text: &str) -> Frame {
+        let lines: Vec<&str> = text.lines().map(|l| l.trim_end_matches('\r')).collect();
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let mut frame = Frame::new(width, lines.len(), ' ');
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                frame.set(x, y, c);
+            }
+        }
+        frame
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, x: usize, y: usize) -> char {
+        self.cells[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, c: char) {
+        self.cells[y * self.width + x] = c;
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &[char]> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    /// Run-length encoding of the frame, rows separated by '/'. A run of one
+    /// cell is written without a count, so "3#./L" is "###." then "L".
+    fn rle(&self) -> String {
+        let mut encoded = Vec::new();
+        for row in self.rows() {
+            let mut s = String::new();
+            let mut i = 0;
+            while i < row.len() {
+                let run = row[i..].iter().take_while(|c| **c == row[i]).count();
+                if run > 1 {
+                    s += &run.to_string();
+                }
+                s.push(row[i]);
+                i += run;
+            }
+            encoded.push(s);
+        }
+        encoded.join("/")
+    }
+
+    /// Binary PPM (`P6`) or PGM (`P5`) image of the frame, one pixel per cell.
+    fn to_pnm(&self, color: bool) -> Vec<u8> {
+        let magic = if color { "P6" } else { "P5" };
+        let mut bytes = format!("{}\n{} {}\n255\n", magic, self.width, self.height).into_bytes();
+        for c in &self.cells {
+            let (r, g, b) = shade(*c);
+            if color {
+                bytes.extend(&[r, g, b]);
+            } else {
+                let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+                bytes.push(luma as u8);
+            }
+        }
+        bytes
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows() {
+            let line: String = row.iter().collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+fn shade(c: char) -> (u8, u8, u8) {
+    match c {
+        '#' => (255, 255, 255),
+        'L' => (64, 160, 64),
+        '+' | '*' => (96, 96, 96),
+        ' ' => (32, 32, 32),
+        _ => (0, 0, 0),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameFormat {
+    /// Escape codes that redraw the terminal for each frame.
+    Ansi,
+    /// One colour image per frame.
+    Ppm,
+    /// One greyscale image per frame.
+    Pgm,
+    /// Every frame run-length encoded on one line of a single file.
+    Text,
+}
+
+impl FromStr for FrameFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FrameFormat, String> {
+        match s {
+            "ansi" => Ok(FrameFormat::Ansi),
+            "ppm" => Ok(FrameFormat::Ppm),
+            "pgm" => Ok(FrameFormat::Pgm),
+            "text" => Ok(FrameFormat::Text),
+            _ => Err(format!(
+                "Unknown frame format {:?}, expected ansi, ppm, pgm or text",
+                s
+            )),
+        }
+    }
+}
+
+/// Collects one frame per generation of an automaton.
+#[derive(Debug, Default)]
+pub struct FrameRecorder {
+    frames: Vec<Frame>,
+}
+
+impl FrameRecorder {
+    pub fn new() -> FrameRecorder {
+        FrameRecorder { frames: Vec::new() }
+    }
+
+    pub fn record(&mut self, frame: Frame) {
+        self.frames.push(frame);
+    }
+
+    #[allow(dead_code)]
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Plays the frames as a terminal animation, clearing the screen before
+    /// each one and pausing `delay` after it.
+    pub fn play<W: Write>(&self, out: &mut W, delay: Duration) -> io::Result<()> {
+        for (generation, frame) in self.frames.iter().enumerate() {
+            write!(out, "\x1b[2J\x1b[H")?;
+            writeln!(out, "Generation {}", generation)?;
+            write!(out, "{}", frame)?;
+            out.flush()?;
+            thread::sleep(delay);
+        }
+        Ok(())
+    }
+
+    /// One line per generation: its number, size and run-length encoding.
+    pub fn text_log(&self) -> String {
+        let mut log = String::new();
+        for (generation, frame) in self.frames.iter().enumerate() {
+            log += &format!(
+                "{} {}x{} {}\n",
+                generation,
+                frame.width,
+                frame.height,
+                frame.rle()
+            );
+        }
+        log
+    }
+
+    /// Writes the frames into `dir`, creating it if needed. Images are
+    /// numbered `frame0000.ppm` and so on; the other formats go to a single
+    /// `frames.ans` or `frames.txt`.
+    pub fn save(&self, dir: &Path, format: FrameFormat) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        match format {
+            FrameFormat::Ansi => {
+                let mut file = File::create(dir.join("frames.ans"))?;
+                self.play(&mut file, Duration::from_secs(0))
+            }
+            FrameFormat::Ppm | FrameFormat::Pgm => {
+                let (color, ext) = match format {
+                    FrameFormat::Ppm => (true, "ppm"),
+                    _ => (false, "pgm"),
+                };
+                for (generation, frame) in self.frames.iter().enumerate() {
+                    let path = dir.join(format!("frame{:04}.{}", generation, ext));
+                    fs::write(path, frame.to_pnm(color))?;
+                }
+                Ok(())
+            }
+            FrameFormat::Text => fs::write(dir.join("frames.txt"), self.text_log()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Frame, FrameFormat, FrameRecorder};
+
+    #[test]
+    fn test_case() {
+        let frame = Frame::from_text("#L.\n##\n");
+        assert_eq!((frame.width, frame.height), (3, 2));
+        assert_eq!(frame.get(2, 1), ' ');
+        assert_eq!(frame.to_string(), "#L.\n## \n");
+        assert_eq!(frame.rle(), "#L./2# ");
+
+        let pgm = frame.to_pnm(false);
+        assert!(pgm.starts_with(b"P5\n3 2\n255\n"));
+        assert_eq!(pgm.len(), "P5\n3 2\n255\n".len() + 6);
+        assert_eq!(&pgm[pgm.len() - 6..], &[255, 120, 0, 255, 255, 32]);
+        assert_eq!(frame.to_pnm(true).len(), "P6\n3 2\n255\n".len() + 18);
+
+        let mut recorder = FrameRecorder::new();
+        recorder.record(frame);
+        recorder.record(Frame::new(4, 1, '.'));
+        assert_eq!(recorder.text_log(), "0 3x2 #L./2# \n1 4x1 4.\n");
+
+        let mut out = Vec::new();
+        recorder.play(&mut out, Duration::from_secs(0)).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("\x1b[2J").count(), 2);
+        assert!(out.ends_with("Generation 1\n....\n"));
+
+        assert_eq!("pgm".parse(), Ok(FrameFormat::Pgm));
+        assert!("gif".parse::<FrameFormat>().is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Where an automaton started repeating itself: generation `first_repeat`
+/// is identical to generation `first_repeat - period`, and from there on
+/// the states go round every `period` generations. A fixed point is a
+/// cycle of period 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cycle {
+    pub first_repeat: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    /// First generation of the repeating part.
+    pub fn start(&self) -> usize {
+        self.first_repeat - self.period
+    }
+}
+
+/// Every generation of an automaton, indexed by hash so the first repeat
+/// is found as soon as it's pushed.
+#[derive(Debug)]
+pub struct History<S> {
+    states: Vec<S>,
+    seen: HashMap<u64, Vec<usize>>,
+    cycle: Option<Cycle>,
+}
+
+fn hash_of<S: Hash>(state: &S) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<S: Hash + Eq> History<S> {
+    pub fn new(initial: S) -> History<S> {
+        let mut seen = HashMap::new();
+        seen.insert(hash_of(&initial), vec![0]);
+        History {
+            states: vec![initial],
+            seen,
+            cycle: None,
+        }
+    }
+
+    /// Records the next generation, returning the cycle once one shows up.
+    /// Nothing more is recorded after that, since every later generation
+    /// is already known.
+    pub fn push(&mut self, state: S) -> Option<Cycle> {
+        if self.cycle.is_some() {
+            return self.cycle;
+        }
+        let generation = self.states.len();
+        let states = &mut self.states;
+        let bucket = self.seen.entry(hash_of(&state)).or_default();
+        if let Some(earlier) = bucket.iter().find(|g| states[**g] == state) {
+            self.cycle = Some(Cycle {
+                first_repeat: generation,
+                period: generation - earlier,
+            });
+        } else {
+            bucket.push(generation);
+            states.push(state);
+        }
+        self.cycle
+    }
+
+    #[allow(dead_code)]
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+
+    /// Number of distinct generations recorded.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn latest(&self) -> &S {
+        &self.states[self.states.len() - 1]
+    }
+
+    /// Generation `n`, if it has been recorded or a cycle has been found
+    /// that predicts it.
+    pub fn generation(&self, n: usize) -> Option<&S> {
+        if n < self.states.len() {
+            return self.states.get(n);
+        }
+        let cycle = self.cycle?;
+        let start = cycle.start();
+        self.states.get(start + (n - start) % cycle.period)
+    }
+
+    /// Steps from `initial` until generation `n` is known, stopping early
+    /// if the states start repeating.
+    pub fn run<F: FnMut(&S) -> S>(initial: S, n: usize, mut step: F) -> History<S> {
+        let mut history = History::new(initial);
+        while history.len() <= n {
+            let next = step(history.latest());
+            if history.push(next).is_some() {
+                break;
+            }
+        }
+        history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cycle, History};
+
+    #[test]
+    fn test_case() {
+        // 0, 1, 2, 3, 4, 2, 3, 4, ...
+        let step = |x: &u32| if *x == 4 { 2 } else { x + 1 };
+        let history = History::run(0, 1_000_000_000, step);
+        assert_eq!(
+            history.cycle(),
+            Some(Cycle {
+                first_repeat: 5,
+                period: 3
+            })
+        );
+        assert_eq!(history.len(), 5);
+        assert_eq!(history.generation(4), Some(&4));
+        assert_eq!(history.generation(5), Some(&2));
+        assert_eq!(history.generation(1_000_000_000), Some(&4));
+
+        let history = History::run(10, 3, |x: &u32| x + 1);
+        assert_eq!(history.cycle(), None);
+        assert_eq!(history.generation(3), Some(&13));
+        assert_eq!(history.generation(4), None);
+
+        let mut history = History::new(vec![1, 1]);
+        assert_eq!(history.push(vec![0, 1]), None);
+        let fixed = history.push(vec![0, 1]).unwrap();
+        assert_eq!((fixed.first_repeat, fixed.period, fixed.start()), (2, 1, 1));
+        assert_eq!(history.push(vec![7]), Some(fixed));
+        assert_eq!(history.generation(50), Some(&vec![0, 1]));
+    }
+}
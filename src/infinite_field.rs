@@ -0,0 +1,398 @@
+use std::fmt;
+
+/// One orthant of an `InfiniteField`: a dense box of cells at non-negative
+/// coordinates, growing to fit whatever is set. The first coordinate
+/// varies fastest in `field`. A dimension that's too small at least
+/// doubles, so setting cells one further out each time doesn't copy the
+/// whole box on every write.
+#[derive(Debug, Clone)]
+pub struct Quad<T, const D: usize> {
+    field: Vec<T>,
+    sizes: [usize; D],
+}
+
+impl<T: Default + Copy, const D: usize> Quad<T, D> {
+    pub fn new() -> Self {
+        Self {
+            field: vec![],
+            sizes: [0; D],
+        }
+    }
+
+    fn index(sizes: &[usize; D], pos: &[usize; D]) -> usize {
+        let mut idx = 0;
+        for k in (0..D).rev() {
+            idx = idx * sizes[k] + pos[k];
+        }
+        idx
+    }
+
+    fn position(sizes: &[usize; D], mut idx: usize) -> [usize; D] {
+        let mut pos = [0; D];
+        for k in 0..D {
+            pos[k] = idx % sizes[k];
+            idx /= sizes[k];
+        }
+        pos
+    }
+
+    pub fn get(&self, pos: [usize; D]) -> T {
+        if (0..D).any(|k| pos[k] >= self.sizes[k]) {
+            T::default()
+        } else {
+            // In bounds, get val
+            self.field[Self::index(&self.sizes, &pos)]
+        }
+    }
+
+    pub fn set(&mut self, pos: [usize; D], val: T) {
+        if (0..D).any(|k| pos[k] >= self.sizes[k]) {
+            // Expand in every direction that's too small at once
+            let mut newsizes = self.sizes;
+            for k in 0..D {
+                if pos[k] >= newsizes[k] {
+                    newsizes[k] = (pos[k] + 1).max(2 * newsizes[k]);
+                }
+            }
+            let mut newfield = vec![T::default(); newsizes.iter().product()];
+            for (idx, v) in self.field.iter().enumerate() {
+                let old = Self::position(&self.sizes, idx);
+                newfield[Self::index(&newsizes, &old)] = *v;
+            }
+            self.field = newfield;
+            self.sizes = newsizes;
+        }
+        let idx = Self::index(&self.sizes, &pos);
+        self.field[idx] = val;
+    }
+}
+
+/// Every point in the box from `lo` to `hi` inclusive, first coordinate
+/// varying fastest.
+pub fn points_between<const D: usize>(lo: [isize; D], hi: [isize; D]) -> Vec<[isize; D]> {
+    if (0..D).any(|k| lo[k] > hi[k]) {
+        return vec![];
+    }
+    let mut points = Vec::new();
+    let mut pos = lo;
+    loop {
+        points.push(pos);
+        let mut k = 0;
+        while k < D && pos[k] == hi[k] {
+            pos[k] = lo[k];
+            k += 1;
+        }
+        if k == D {
+            return points;
+        }
+        pos[k] += 1;
+    }
+}
+
+/// Offsets to the 3^D - 1 cells touching a cell, diagonals included.
+pub fn neighborhood<const D: usize>() -> Vec<[isize; D]> {
+    points_between([-1; D], [1; D])
+        .into_iter()
+        .filter(|p| p.iter().any(|c| *c != 0))
+        .collect()
+}
+
+/// Widens `lims` so each range reaches one past `pos` on either side.
+pub fn widen_lims<const D: usize>(lims: &mut [(i64, i64); D], pos: &[isize; D]) {
+    for (lim, c) in lims.iter_mut().zip(pos.iter()) {
+        let c = *c as i64;
+        if c < lim.0 {
+            lim.0 = c - 1;
+        }
+        if c > lim.1 {
+            lim.1 = c + 1;
+        }
+    }
+}
+
+/// Storage for a grid of `T` in `D` dimensions with no edges, where every
+/// cell starts out as `T::default()`.
+pub trait Field<T: Default + Copy + std::ops::Add<Output = T>, const D: usize> {
+    fn new() -> Self;
+
+    fn get(&self, pos: [isize; D]) -> T;
+
+    fn set(&mut self, pos: [isize; D], v: T);
+
+    /// Every cell that isn't `T::default()`, with its position.
+    fn cells(&self) -> Vec<([isize; D], T)>;
+
+    /// Per dimension, a range one wider on each side than the coordinates
+    /// set so far.
+    fn lims(&self) -> [(i64, i64); D];
+
+    /// Only the tests still add up neighbours one cell at a time; day 17
+    /// scatters counts from the active cells instead.
+    #[cfg(test)]
+    fn num_neighbors(&self, pos: [isize; D]) -> T {
+        let mut sum = T::default();
+        for offset in neighborhood::<D>() {
+            let mut p = pos;
+            for k in 0..D {
+                p[k] += offset[k];
+            }
+            sum = sum + self.get(p);
+        }
+        sum
+    }
+
+    #[cfg(test)]
+    fn sum(&self) -> T {
+        self.cells()
+            .iter()
+            .fold(T::default(), |sum, (_, v)| sum + *v)
+    }
+}
+
+/// A grid of `T` in `D` dimensions with no edges, where every cell starts
+/// out as `T::default()`. Each of the 2^D orthants is a `Quad` indexed by
+/// the absolute values of the coordinates.
+#[derive(Debug, Clone)]
+pub struct InfiniteField<T, const D: usize> {
+    quads: Vec<Quad<T, D>>,
+    lims: [(i64, i64); D],
+}
+
+impl<T, const D: usize> InfiniteField<T, D> {
+    /// Which orthant `pos` is in, and where in it.
+    fn locate(pos: &[isize; D]) -> (usize, [usize; D]) {
+        let mut idx = 0;
+        let mut abs = [0; D];
+        for k in 0..D {
+            if pos[k] < 0 {
+                idx += 1 << k;
+            }
+            abs[k] = pos[k].unsigned_abs();
+        }
+        (idx, abs)
+    }
+}
+
+impl<T: Default + Copy + std::ops::Add<Output = T> + std::cmp::PartialEq, const D: usize>
+    Field<T, D> for InfiniteField<T, D>
+{
+    fn new() -> Self {
+        let quads = vec![Quad::<T, D>::new(); 1 << D];
+        Self {
+            quads,
+            lims: [(0, 0); D],
+        }
+    }
+
+    fn get(&self, pos: [isize; D]) -> T {
+        let (idx, abs) = Self::locate(&pos);
+        self.quads[idx].get(abs)
+    }
+
+    fn set(&mut self, pos: [isize; D], v: T) {
+        widen_lims(&mut self.lims, &pos);
+        let (idx, abs) = Self::locate(&pos);
+        self.quads[idx].set(abs, v);
+    }
+
+    fn cells(&self) -> Vec<([isize; D], T)> {
+        let mut cells = Vec::new();
+        for (idx, quad) in self.quads.iter().enumerate() {
+            for (i, v) in quad.field.iter().enumerate() {
+                if *v == T::default() {
+                    continue;
+                }
+                let abs = Quad::<T, D>::position(&quad.sizes, i);
+                let mut pos = [0; D];
+                for k in 0..D {
+                    pos[k] = if idx & (1 << k) != 0 {
+                        -(abs[k] as isize)
+                    } else {
+                        abs[k] as isize
+                    };
+                }
+                cells.push((pos, *v));
+            }
+        }
+        cells
+    }
+
+    fn lims(&self) -> [(i64, i64); D] {
+        self.lims
+    }
+}
+
+fn layer_name(k: usize) -> String {
+    match k {
+        2 => "Z".to_string(),
+        3 => "W".to_string(),
+        _ => format!("D{}", k),
+    }
+}
+
+impl<T: Default + Copy + std::ops::Add<Output = T> + std::cmp::PartialEq, const D: usize>
+    fmt::Display for InfiniteField<T, D>
+{
+    // This trait requires `fmt` with this exact signature.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lo = [0; D];
+        let mut hi = [0; D];
+        for k in 0..D {
+            lo[k] = (self.lims[k].0 - 1) as isize;
+            hi[k] = (self.lims[k].1 + 1) as isize;
+        }
+        let mut s = String::new();
+        for p in points_between(lo, hi) {
+            if D > 1 && p[0] == lo[0] && p[1] == lo[1] {
+                // Start of a layer: name every higher dimension that has
+                // just moved on, outermost first.
+                for k in (2..D).rev() {
+                    if (2..k).all(|j| p[j] == lo[j]) {
+                        s += &format!("{} layer {:?}:\n", layer_name(k), p[k]);
+                    }
+                }
+            }
+            if self.get(p) == T::default() {
+                if p.iter().all(|c| *c == 0) {
+                    s += "+";
+                } else if p.iter().take(2).all(|c| *c == 0) {
+                    s += "*";
+                } else {
+                    s += ".";
+                }
+            } else {
+                s += "#";
+            }
+            if p[0] == hi[0] {
+                s += "\n";
+            }
+        }
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use std::{cell::RefCell, collections::HashMap};
+
+    use super::{points_between, Field, InfiniteField, Quad};
+
+    #[test]
+    fn test_case() {
+        let grid = RefCell::new(InfiniteField::<i64, 3>::new());
+        let answers: RefCell<HashMap<(isize, isize, isize), i64>> = RefCell::new(HashMap::new());
+
+        proptest!(|(x in -100isize..100, y in -100isize..100, z in -100isize..100, v in -42i64..42)| {
+            grid.borrow_mut().set([x, y, z], v);
+
+            prop_assert!(grid.borrow().lims[0].0 <= x as i64);
+            prop_assert!(grid.borrow().lims[0].1 >= x as i64);
+            prop_assert!(grid.borrow().lims[1].0 <= y as i64);
+            prop_assert!(grid.borrow().lims[1].1 >= y as i64);
+            prop_assert!(grid.borrow().lims[2].0 <= z as i64);
+            prop_assert!(grid.borrow().lims[2].1 >= z as i64);
+
+            let mut myanswers = answers.borrow_mut();
+            myanswers.insert((x,y,z), v);
+        });
+    }
+
+    #[test]
+    fn test_case_4d() {
+        let grid = RefCell::new(InfiniteField::<i8, 4>::new());
+        let answers: RefCell<HashMap<(isize, isize, isize, isize), i8>> =
+            RefCell::new(HashMap::new());
+
+        proptest!(|(x in -10isize..10, y in -10isize..10, z in -10isize..10, w in -10isize..10, v in -8i8..8)| {
+            grid.borrow_mut().set([x, y, z, w], v);
+
+            prop_assert!(grid.borrow().lims[0].0 <= x as i64);
+            prop_assert!(grid.borrow().lims[0].1 >= x as i64);
+            prop_assert!(grid.borrow().lims[1].0 <= y as i64);
+            prop_assert!(grid.borrow().lims[1].1 >= y as i64);
+            prop_assert!(grid.borrow().lims[2].0 <= z as i64);
+            prop_assert!(grid.borrow().lims[2].1 >= z as i64);
+            prop_assert!(grid.borrow().lims[3].0 <= w as i64);
+            prop_assert!(grid.borrow().lims[3].1 >= w as i64);
+
+            let mut myanswers = answers.borrow_mut();
+            myanswers.insert((x,y,z,w), v);
+        });
+    }
+
+    #[test]
+    fn test_quad_growth() {
+        let mut quad = Quad::<i64, 2>::new();
+        for x in 0..10 {
+            quad.set([x, 0], x as i64);
+        }
+        assert_eq!(quad.sizes, [16, 1]);
+        quad.set([2, 5], 7);
+        assert_eq!(quad.sizes, [16, 6]);
+        for x in 0..10 {
+            assert_eq!(quad.get([x, 0]), x as i64);
+        }
+        assert_eq!(quad.get([2, 5]), 7);
+        assert_eq!(quad.get([20, 0]), 0);
+    }
+
+    #[test]
+    fn test_any_dimension() {
+        assert_eq!(
+            points_between([0, 5], [1, 6]),
+            vec![[0, 5], [1, 5], [0, 6], [1, 6]]
+        );
+        assert_eq!(points_between([1], [0]), Vec::<[isize; 1]>::new());
+
+        proptest!(|(cells in prop::collection::hash_map(prop::array::uniform5(-4isize..4), 1i64..9, 0..20))| {
+            let mut field = InfiniteField::<i64, 5>::new();
+            for (pos, v) in &cells {
+                field.set(*pos, *v);
+            }
+            for (pos, v) in &cells {
+                prop_assert_eq!(field.get(*pos), *v);
+                let neighbors: i64 = cells
+                    .iter()
+                    .filter(|(p, _)| *p != pos && (0..5).all(|k| (p[k] - pos[k]).abs() <= 1))
+                    .map(|(_, v)| v)
+                    .sum();
+                prop_assert_eq!(field.num_neighbors(*pos), neighbors);
+            }
+            prop_assert_eq!(field.sum(), cells.values().sum::<i64>());
+            let mut found = field.cells();
+            found.sort_unstable();
+            let mut expected: Vec<([isize; 5], i64)> = cells.into_iter().collect();
+            expected.sort_unstable();
+            prop_assert_eq!(found, expected);
+        });
+
+        let mut field = InfiniteField::<i64, 3>::new();
+        field.set([1, 0, 0], 1);
+        field.set([0, 0, -1], 1);
+        assert_eq!(
+            field.to_string(),
+            "Z layer -3:
+.....
+.*...
+.....
+Z layer -2:
+.....
+.*...
+.....
+Z layer -1:
+.....
+.#...
+.....
+Z layer 0:
+.....
+.+#..
+.....
+Z layer 1:
+.....
+.*...
+.....
+"
+        );
+    }
+}
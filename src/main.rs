@@ -6,6 +6,8 @@ extern crate pest_derive;
 #[macro_use]
 extern crate lazy_static;
 
+use std::path::PathBuf;
+use std::time::Duration;
 use structopt::StructOpt;
 
 mod day01;
@@ -33,8 +35,10 @@ mod day22;
 mod day23;
 mod day24;
 mod day25;
-mod infinite_field_3d;
-mod infinite_field_4d;
+mod frames;
+mod history;
+mod infinite_field;
+mod sparse_field;
 mod util;
 
 #[derive(Debug, StructOpt)]
@@ -42,6 +46,75 @@ mod util;
 struct Opt {
     #[structopt(short, long)]
     day: i64,
+
+    /// Write a Graphviz DOT graph of the puzzle to this file (days 7 and 10)
+    #[structopt(long, parse(from_os_str))]
+    dot: Option<PathBuf>,
+
+    /// Start an interactive debugger on stdin after solving (day 8)
+    #[structopt(long)]
+    debug: bool,
+
+    /// Length of the XMAS preamble (day 9)
+    #[structopt(long, default_value = "25")]
+    preamble: usize,
+
+    /// Extra seating rules to run, e.g. "neighbors=sight:3 free=4" (day 11)
+    #[structopt(long)]
+    seating: Option<String>,
+
+    /// Write the ship's part B route to this file, as SVG if it ends in
+    /// .svg and as plain text otherwise (day 12)
+    #[structopt(long, parse(from_os_str))]
+    route: Option<PathBuf>,
+
+    /// Print every memory write with the value or addresses it really
+    /// touches (day 14)
+    #[structopt(long)]
+    trace: bool,
+
+    /// Print which rules every nearby ticket value matches, and how much
+    /// of the tickets each rule covers (day 16)
+    #[structopt(long)]
+    scan: bool,
+
+    /// Also boot the cubes in this many dimensions, from 2 to 6 (day 17)
+    #[structopt(long)]
+    dimensions: Option<usize>,
+
+    /// Time the cube simulation with dense and sparse fields in 3 to 6
+    /// dimensions, keeping the best of this many runs after a warm-up
+    /// (day 17)
+    #[structopt(long)]
+    bench: Option<usize>,
+
+    /// Record every generation into this directory (days 11, 17 and 24)
+    #[structopt(long, parse(from_os_str))]
+    frames: Option<PathBuf>,
+
+    /// How to write frames: ansi, ppm, pgm or text
+    #[structopt(long, default_value = "text")]
+    frame_format: frames::FrameFormat,
+
+    /// Milliseconds between frames when playing an ansi animation
+    #[structopt(long, default_value = "100")]
+    frame_delay: u64,
+}
+
+/// Saves a part's frames under `<frames>/<part>`, and plays them in the
+/// terminal as well for the ansi format.
+fn save_frames(opt: &Opt, part: char, recorder: &frames::FrameRecorder) {
+    if let Some(dir) = &opt.frames {
+        if opt.frame_format == frames::FrameFormat::Ansi {
+            let delay = Duration::from_millis(opt.frame_delay);
+            recorder
+                .play(&mut std::io::stdout(), delay)
+                .expect("Something went wrong playing the frames");
+        }
+        recorder
+            .save(&dir.join(part.to_string()), opt.frame_format)
+            .expect("Something went wrong writing the frames");
+    }
 }
 
 fn main() {
@@ -95,6 +168,9 @@ fn main() {
             let a = day07::day07(&lines, 'a');
             let b = day07::day07(&lines, 'b');
             println!("Day 7: A: {:?}, B: {:?}", a, b);
+            if let Some(path) = &opt.dot {
+                util::write_contents(path, &day07::day07_dot(&lines));
+            }
         }
         8 => {
             // Day 8
@@ -102,20 +178,26 @@ fn main() {
             let a = day08::day08(&lines, 'a');
             let b = day08::day08(&lines, 'b');
             println!("Day 8: A: {:?}, B: {:?}", a, b);
+            if opt.debug {
+                day08::day08_debug(&lines);
+            }
         }
         9 => {
             // Day 9
             let nums = util::load_nums("inputs/day09.txt");
-            let a = day09::day09(&nums, 'a');
-            let b = day09::day09(&nums, 'b');
+            let a = day09::day09(&nums, 'a', opt.preamble);
+            let b = day09::day09(&nums, 'b', opt.preamble);
             println!("Day 9: A: {:?}, B: {:?}", a, b);
         }
         10 => {
             // Day 10
             let nums = util::load_nums("inputs/day10.txt");
             let a = day10::day10(&nums, 'a');
-            let b = day10::day10(&nums, 'b');
-            println!("Day 10: A: {:?}, B: {:?}", a, b);
+            let b = day10::day10_count(&nums);
+            println!("Day 10: A: {:?}, B: {}", a, b);
+            if let Some(path) = &opt.dot {
+                util::write_contents(path, &day10::day10_dot(&nums));
+            }
         }
         11 => {
             // Day 11
@@ -123,13 +205,31 @@ fn main() {
             let a = day11::day11(&lines, 'a');
             let b = day11::day11(&lines, 'b');
             println!("Day 11: A: {:?}, B: {:?}", a, b);
+            if let Some(spec) = &opt.seating {
+                match day11::day11_with_rules(&lines, spec) {
+                    Ok(n) => println!("Day 11: {:?}: {:?}", spec, n),
+                    Err(e) => println!("Day 11: {}", e),
+                }
+            }
+            if opt.frames.is_some() {
+                save_frames(&opt, 'a', &day11::day11_frames(&lines, 'a'));
+                save_frames(&opt, 'b', &day11::day11_frames(&lines, 'b'));
+            }
         }
         12 => {
             // Day 12
             let lines = util::load_strings("inputs/day12.txt");
-            let a = day12::day12(&lines, 'a');
-            let b = day12::day12(&lines, 'b');
-            println!("Day 12: A: {:?}, B: {:?}", a, b);
+            match (day12::day12(&lines, 'a'), day12::day12(&lines, 'b')) {
+                (Ok(a), Ok(b)) => println!("Day 12: A: {:?}, B: {:?}", a, b),
+                (Err(e), _) | (_, Err(e)) => println!("Day 12: {}", e),
+            }
+            if let Some(path) = &opt.route {
+                let svg = matches!(path.extension(), Some(ext) if ext == "svg");
+                match day12::day12_route(&lines, 'b', svg) {
+                    Ok(route) => util::write_contents(path, &route),
+                    Err(e) => println!("Day 12: {}", e),
+                }
+            }
         }
         13 => {
             // Day 13
@@ -144,6 +244,18 @@ fn main() {
             let a = day14::day14(&lines, 'a');
             let b = day14::day14(&lines, 'b');
             println!("Day 14: A: {:?}, B: {:?}", a, b);
+            if opt.trace {
+                for part in &['a', 'b'] {
+                    match day14::day14_trace(&lines, *part) {
+                        Ok(trace) => {
+                            for line in trace {
+                                println!("{}: {}", part, line);
+                            }
+                        }
+                        Err(e) => println!("Day 14: {}", e),
+                    }
+                }
+            }
         }
         15 => {
             // Day 15
@@ -158,6 +270,9 @@ fn main() {
             let a = day16::day16(&contents, 'a');
             let b = day16::day16(&contents, 'b');
             println!("Day 16: A: {:?}, B: {:?}", a, b);
+            if opt.scan {
+                print!("{}", day16::day16_report(&contents));
+            }
         }
         17 => {
             // Day 17
@@ -165,6 +280,21 @@ fn main() {
             let a = day17::day17(&contents, 'a');
             let b = day17::day17(&contents, 'b');
             println!("Day 17: A: {:?}, B: {:?}", a, b);
+            if let Some(dimensions) = opt.dimensions {
+                match day17::day17_dimensions(&contents, dimensions) {
+                    Some(n) => println!("Day 17: {}D: {:?}", dimensions, n),
+                    None => println!("Day 17: can't boot in {} dimensions", dimensions),
+                }
+            }
+            if let Some(runs) = opt.bench {
+                for line in day17::day17_bench(&contents, runs) {
+                    println!("Day 17: {}", line);
+                }
+            }
+            if opt.frames.is_some() {
+                save_frames(&opt, 'a', &day17::day17_frames(&contents, 'a'));
+                save_frames(&opt, 'b', &day17::day17_frames(&contents, 'b'));
+            }
         }
         18 => {
             // Day 18
@@ -218,6 +348,9 @@ fn main() {
             let a = day24::day24a(&lines);
             let b = day24::day24b(&lines);
             println!("Day 24: A: {:?}, B: {:?}", a, b);
+            if opt.frames.is_some() {
+                save_frames(&opt, 'b', &day24::day24_frames(&lines, 100));
+            }
         }
         25 => {
             // Day 25
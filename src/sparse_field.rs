@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use crate::infinite_field::{widen_lims, Field};
+
+/// A field that only stores the cells that aren't `T::default()`, so its
+/// size follows how many cells are set rather than how far apart they are.
+#[derive(Debug, Clone)]
+pub struct SparseField<T, const D: usize> {
+    cells: HashMap<[isize; D], T>,
+    lims: [(i64, i64); D],
+}
+
+impl<T: Default + Copy + std::ops::Add<Output = T> + std::cmp::PartialEq, const D: usize>
+    Field<T, D> for SparseField<T, D>
+{
+    fn new() -> Self {
+        Self {
+            cells: HashMap::new(),
+            lims: [(0, 0); D],
+        }
+    }
+
+    fn get(&self, pos: [isize; D]) -> T {
+        self.cells.get(&pos).copied().unwrap_or_default()
+    }
+
+    fn set(&mut self, pos: [isize; D], v: T) {
+        widen_lims(&mut self.lims, &pos);
+        if v == T::default() {
+            self.cells.remove(&pos);
+        } else {
+            self.cells.insert(pos, v);
+        }
+    }
+
+    fn cells(&self) -> Vec<([isize; D], T)> {
+        self.cells.iter().map(|(pos, v)| (*pos, *v)).collect()
+    }
+
+    fn lims(&self) -> [(i64, i64); D] {
+        self.lims
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::SparseField;
+    use crate::infinite_field::{Field, InfiniteField};
+
+    #[test]
+    fn test_case() {
+        let write = (prop::array::uniform4(-5isize..5), 0i64..3);
+        proptest!(|(writes in prop::collection::vec(write, 0..40), probe in prop::array::uniform4(-6isize..6))| {
+            let mut sparse = SparseField::<i64, 4>::new();
+            let mut dense = InfiniteField::<i64, 4>::new();
+            for (pos, v) in &writes {
+                sparse.set(*pos, *v);
+                dense.set(*pos, *v);
+            }
+            prop_assert_eq!(sparse.get(probe), dense.get(probe));
+            prop_assert_eq!(sparse.num_neighbors(probe), dense.num_neighbors(probe));
+            prop_assert_eq!(sparse.sum(), dense.sum());
+            prop_assert_eq!(sparse.lims(), dense.lims());
+            let mut stored = sparse.cells();
+            stored.sort_unstable();
+            let mut expected = dense.cells();
+            expected.sort_unstable();
+            prop_assert_eq!(sparse.cells.len(), expected.len());
+            prop_assert_eq!(stored, expected);
+        });
+    }
+}
@@ -1,9 +1,14 @@
 use std::fs;
+use std::path::Path;
 
 pub fn load_contents(path: &str) -> String {
     fs::read_to_string(path).expect("Something went wrong reading the file")
 }
 
+pub fn write_contents(path: &Path, contents: &str) {
+    fs::write(path, contents).expect("Something went wrong writing the file")
+}
+
 pub fn load_strings(path: &str) -> Vec<String> {
     let contents = load_contents(path);
     let lines = contents.split('\n');